@@ -0,0 +1,292 @@
+//! Blocking commands, e.g. `BLPOP`, served by connections of their own
+use std::cell::RefCell;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{self, Poll};
+use std::time::Duration;
+
+use actix::dev::{MessageResponse, ResponseChannel};
+use actix::prelude::*;
+use futures::{future, stream, FutureExt, SinkExt, StreamExt};
+use redis_async::resp::RespValue;
+use tokio::time::Interval;
+use tokio_util::codec::Framed;
+
+use crate::command::{Asking, Command};
+use crate::redis::{self, ConnectOptions};
+use crate::resp3::{Resp3Codec, Resp3Value};
+use crate::transport::Stream;
+use crate::Error;
+
+/// How often a blocking command checks whether its caller is still waiting
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Connections of blocking commands to a single server.
+///
+/// A blocking command holds its connection until the reply arrives, so each
+/// of the concurrent commands gets its own connection. Up to `max_idle`
+/// connections are kept open for the next ones.
+///
+/// The address is given by `RedisActor` on every request, as it is not known
+/// before the sentinels tell the master.
+pub(crate) struct BlockingPool {
+    options: ConnectOptions,
+    idle: Rc<RefCell<Vec<Framed<Stream, Resp3Codec>>>>,
+    max_idle: usize,
+}
+
+impl BlockingPool {
+    pub(crate) fn new(options: ConnectOptions, max_idle: usize) -> Self {
+        BlockingPool {
+            options,
+            idle: Rc::new(RefCell::new(vec![])),
+            max_idle,
+        }
+    }
+
+    /// Close the idle connections
+    pub(crate) fn clear(&self) {
+        self.idle.borrow_mut().clear();
+    }
+
+    /// Send `req` over an idle or new connection to `addr` and wait for the
+    /// reply within `timeout`. `ASKING` is written along with `req` if `asking`.
+    pub(crate) fn request(
+        &self,
+        addr: String,
+        req: RespValue,
+        timeout: Option<Duration>,
+        asking: bool,
+    ) -> ResponseFuture<Result<Resp3Value, Error>> {
+        let options = self.options.clone();
+        let idle = self.idle.clone();
+        let max_idle = self.max_idle;
+
+        Box::pin(async move {
+            let reused = idle.borrow_mut().pop();
+            let (mut framed, res) = match reused {
+                Some(mut framed) => {
+                    let res = roundtrip(&mut framed, req.clone(), timeout, asking).await;
+                    match res {
+                        // closed by the server while idle, nothing was executed
+                        Err(Error::Disconnected) | Err(Error::IoError(_)) => {
+                            debug!("Idle blocking connection is gone, reconnecting");
+                            let mut framed = connect(addr, options).await?;
+                            let res = roundtrip(&mut framed, req, timeout, asking).await;
+                            (framed, res)
+                        }
+                        res => (framed, res),
+                    }
+                }
+                None => {
+                    let mut framed = connect(addr, options).await?;
+                    let res = roundtrip(&mut framed, req, timeout, asking).await;
+                    (framed, res)
+                }
+            };
+
+            // the reply of a timed out command may still arrive, so the
+            // connection is dropped instead of reused
+            if res.is_ok() {
+                let mut idle = idle.borrow_mut();
+                if idle.len() < max_idle {
+                    idle.push(framed);
+                }
+            } else {
+                let _ = framed.close().await;
+            }
+            res
+        })
+    }
+}
+
+async fn connect(
+    addr: String,
+    options: ConnectOptions,
+) -> Result<Framed<Stream, Resp3Codec>, Error> {
+    let stream = redis::connect(addr, options).await?;
+    Ok(Framed::new(stream, Resp3Codec))
+}
+
+async fn roundtrip(
+    framed: &mut Framed<Stream, Resp3Codec>,
+    req: RespValue,
+    timeout: Option<Duration>,
+    asking: bool,
+) -> Result<Resp3Value, Error> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, exchange(framed, req, asking))
+            .await
+            .unwrap_or(Err(Error::Timeout)),
+        None => exchange(framed, req, asking).await,
+    }
+}
+
+/// Send `req`, preceded by `ASKING` in the same write if `asking`, and wait
+/// for its reply. A failed `ASKING` fails the request.
+async fn exchange(
+    framed: &mut Framed<Stream, Resp3Codec>,
+    req: RespValue,
+    asking: bool,
+) -> Result<Resp3Value, Error> {
+    if !asking {
+        return redis::roundtrip(framed, req).await;
+    }
+
+    let reqs = vec![Ok(Asking.into_request()), Ok(req)];
+    framed.send_all(&mut stream::iter(reqs)).await?;
+    let mut replies = vec![];
+    while replies.len() < 2 {
+        match framed.next().await {
+            Some(Ok(res)) => replies.push(res),
+            Some(Err(e)) => return Err(e.into()),
+            None => return Err(Error::Disconnected),
+        }
+    }
+    let res = replies.pop().unwrap();
+    let asked = replies.pop().unwrap().into_resp2();
+    Asking::from_response(asked).map_err(Error::Redis)?;
+    Ok(res)
+}
+
+/// Reply of a command.
+///
+/// A blocking command is abandoned once the caller drops its request, closing
+/// the connection so the server stops blocking on behalf of nobody.
+pub struct Cancelable<A: Actor, I> {
+    fut: Response<A, I>,
+    blocking: bool,
+}
+
+enum Response<A: Actor, I> {
+    /// Runs on its own like `ResponseFuture`, surviving restarts of the actor
+    Future(ResponseFuture<I>),
+    /// Runs within the context of the actor like `ResponseActFuture`
+    ActorFuture(ResponseActFuture<A, I>),
+}
+
+impl<A: Actor, I> Cancelable<A, I> {
+    pub(crate) fn future(fut: ResponseFuture<I>, blocking: bool) -> Self {
+        Cancelable {
+            fut: Response::Future(fut),
+            blocking,
+        }
+    }
+
+    pub(crate) fn actor_future(fut: ResponseActFuture<A, I>, blocking: bool) -> Self {
+        Cancelable {
+            fut: Response::ActorFuture(fut),
+            blocking,
+        }
+    }
+}
+
+impl<A, M, I: 'static> MessageResponse<A, M> for Cancelable<A, I>
+where
+    A: Actor,
+    A::Context: AsyncContext<A>,
+    M: Message<Result = I> + 'static,
+{
+    fn handle<R: ResponseChannel<M>>(self, ctx: &mut A::Context, tx: Option<R>) {
+        match (self.fut, tx) {
+            (Response::Future(mut fut), Some(tx)) if self.blocking => {
+                let mut tx = Some(Box::new(tx));
+                let mut check = tokio::time::interval(CANCEL_CHECK_INTERVAL);
+                actix_rt::spawn(future::poll_fn(move |task| {
+                    if let Poll::Ready(res) = fut.as_mut().poll(task) {
+                        if let Some(tx) = tx.take() {
+                            tx.send(res);
+                        }
+                        return Poll::Ready(());
+                    }
+                    if is_canceled(&tx, &mut check, task) {
+                        return Poll::Ready(());
+                    }
+                    Poll::Pending
+                }));
+            }
+            (Response::Future(fut), tx) => {
+                actix_rt::spawn(fut.map(|res| {
+                    if let Some(tx) = tx {
+                        tx.send(res);
+                    }
+                }));
+            }
+            (Response::ActorFuture(fut), Some(tx)) if self.blocking => {
+                ctx.spawn(UntilCanceled {
+                    fut,
+                    tx: Some(Box::new(tx)),
+                    check: tokio::time::interval(CANCEL_CHECK_INTERVAL),
+                });
+            }
+            (Response::ActorFuture(fut), tx) => {
+                ctx.spawn(fut.map(|res, _, _| {
+                    if let Some(tx) = tx {
+                        tx.send(res);
+                    }
+                }));
+            }
+        }
+    }
+}
+
+/// Check whether the receiver of `tx` is gone, every tick of `check`
+fn is_canceled<M, R>(
+    tx: &Option<Box<R>>,
+    check: &mut Interval,
+    task: &mut task::Context<'_>,
+) -> bool
+where
+    M: Message,
+    R: ResponseChannel<M>,
+{
+    while check.poll_tick(task).is_ready() {
+        let canceled = match tx {
+            Some(tx) => tx.is_canceled(),
+            None => true,
+        };
+        if canceled {
+            debug!("Caller is gone, blocking command abandoned");
+            return true;
+        }
+    }
+    false
+}
+
+/// Drive `fut` to completion unless the receiver of `tx` is dropped first
+struct UntilCanceled<A: Actor, M: Message, R> {
+    fut: ResponseActFuture<A, M::Result>,
+    tx: Option<Box<R>>,
+    check: Interval,
+}
+
+impl<A, M, R> ActorFuture for UntilCanceled<A, M, R>
+where
+    A: Actor,
+    M: Message,
+    R: ResponseChannel<M>,
+{
+    type Output = ();
+    type Actor = A;
+
+    fn poll(
+        self: Pin<&mut Self>,
+        act: &mut A,
+        ctx: &mut A::Context,
+        task: &mut task::Context<'_>,
+    ) -> Poll<()> {
+        let this = self.get_mut();
+
+        if let Poll::Ready(res) = Pin::new(&mut this.fut).poll(act, ctx, task) {
+            if let Some(tx) = this.tx.take() {
+                tx.send(res);
+            }
+            return Poll::Ready(());
+        }
+
+        if is_canceled(&this.tx, &mut this.check, task) {
+            return Poll::Ready(());
+        }
+        Poll::Pending
+    }
+}
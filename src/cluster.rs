@@ -1,15 +1,34 @@
 use actix::prelude::*;
-use futures::future::FutureExt;
+use actix_utils::oneshot;
+use futures::future::{self, FutureExt};
 use redis_async::resp::RespValue;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
 
+use crate::blocking::Cancelable;
 use crate::command::*;
-use crate::Error;
-use crate::RedisActor;
+use crate::keyspace::SubscribeKeyspace;
+use crate::listener::Listener;
+use crate::pipeline::Pipeline;
+use crate::pool::{Pool, PoolBuilder, Selection};
+use crate::pubsub::{
+    PubSubMessage, RedisSubscriber, SSubscribe, SUnsubscribe, ShardUnsubscribed,
+};
+use crate::redis::{self, Shutdown};
+use crate::slot::hash_slot;
+use crate::transaction::{self, CheckAndSet, Replies, Transaction};
+use crate::{ConnectOptions, RedisActorBuilder};
+use crate::{Error, RespError};
 
 const MAX_RETRY: usize = 16;
 
+/// Default of `RedisClusterActorBuilder::min_refresh_interval`
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time given to the requests in flight to a node which left the cluster
+const RETIRE_TIMEOUT: Duration = Duration::from_secs(5);
+
 fn fmt_resp_value(o: &::redis_async::resp::RespValue) -> String {
     match o {
         RespValue::Nil => "nil".to_string(),
@@ -24,65 +43,840 @@ fn fmt_resp_value(o: &::redis_async::resp::RespValue) -> String {
     }
 }
 
+/// Which node of a slot serves the read-only commands
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReadPolicy {
+    /// Read from the master like the other commands
+    Master,
+    /// Read from the first replica, or from the master if there is none
+    PreferReplica,
+    /// Read from the replicas in turn, or from the master if there is none
+    RoundRobinReplicas,
+    /// Read from the node, master or replica, with the lowest `PING` latency
+    /// measured when the slots were refreshed
+    Nearest,
+}
+
 pub struct RedisClusterActor {
-    initial_addr: String,
+    /// Nodes to refresh the slots from when none of the known nodes answers
+    seeds: Vec<String>,
+    /// Node which answered the last `CLUSTER SLOTS`, serving the commands
+    /// without keys
+    control_addr: String,
+    connection: PoolBuilder,
     slots: Vec<Slots>,
-    connections: HashMap<String, Addr<RedisActor>>,
+    connections: HashMap<String, Pool>,
+    read_policy: ReadPolicy,
+    /// Connections to the replicas, which issue `READONLY` after connecting
+    replica_connection: PoolBuilder,
+    replicas: HashMap<String, Pool>,
+    next_replica: usize,
+    /// Latency of the nodes, measured for `ReadPolicy::Nearest`
+    latencies: HashMap<String, Duration>,
+    refresh_interval: Option<Duration>,
+    min_refresh_interval: Duration,
+    /// Start of the last refresh of the slots
+    last_refresh: Option<Instant>,
+    refreshing: bool,
+    /// Whether a refresh is delayed by `min_refresh_interval`
+    refresh_scheduled: bool,
+    /// Notified once the next refresh is done
+    refresh_waiters: Vec<oneshot::Sender<()>>,
+    subscribers: HashMap<String, Addr<RedisSubscriber>>,
+    shard_channels: HashMap<String, ShardSubscription>,
+    /// Keyspace subscriptions, made on every master
+    keyspace: Vec<SubscribeKeyspace>,
+    keyspace_nodes: HashSet<String>,
+    shutdown: bool,
+}
+
+/// Command of a `Pipeline` sent to a node
+struct PipelineEntry {
+    /// Position in the pipeline
+    index: usize,
+    request: RespValue,
+    /// Whether to precede the request with `ASKING` after an ASK redirection
+    asking: bool,
+}
+
+/// Shard channel subscribed on a node
+struct ShardSubscription {
+    node: String,
+    listeners: Vec<Listener<PubSubMessage>>,
 }
 
 impl RedisClusterActor {
     pub fn start<S: Into<String>>(addr: S) -> Addr<RedisClusterActor> {
-        let addr = addr.into();
+        Self::builder().start(addr)
+    }
 
-        Supervisor::start(move |_ctx| RedisClusterActor {
-            initial_addr: addr,
-            slots: vec![],
-            connections: HashMap::new(),
-        })
+    /// Start new `Supervisor` with `RedisClusterActor` discovering the
+    /// cluster from any of the `seeds`.
+    pub fn start_seeds<I>(seeds: I) -> Addr<RedisClusterActor>
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        Self::builder().start_seeds(seeds)
     }
 
-    fn refresh_slots(&mut self) -> ResponseActFuture<Self, ()> {
-        let addr = self.initial_addr.clone();
-        let control_connection = self
-            .connections
+    /// Start new `Supervisor` with `RedisClusterActor`.
+    /// `options` are used for the connections to every node of the cluster.
+    pub fn start_with<S: Into<String>>(
+        addr: S,
+        options: ConnectOptions,
+    ) -> Addr<RedisClusterActor> {
+        Self::builder()
+            .connection(RedisActorBuilder::default().options(options))
+            .start(addr)
+    }
+
+    /// Create a builder to configure `RedisClusterActor` before starting it.
+    pub fn builder() -> RedisClusterActorBuilder {
+        RedisClusterActorBuilder::default()
+    }
+
+    /// Get the connections to the node, connecting to it if necessary
+    fn connection(&mut self, addr: String) -> &Pool {
+        let builder = &self.connection;
+        self.connections
             .entry(addr.clone())
-            .or_insert_with(move || RedisActor::start(addr));
+            .or_insert_with(|| builder.start(addr))
+    }
+
+    /// Get the connections to the replica, connecting to it if necessary
+    fn replica_connection(&mut self, addr: String) -> &Pool {
+        let builder = &self.replica_connection;
+        self.replicas
+            .entry(addr.clone())
+            .or_insert_with(|| builder.start(addr))
+    }
+
+    /// Get the subscriber of the node, connecting to it if necessary
+    fn subscriber(
+        &mut self,
+        addr: String,
+        ctx: &mut Context<Self>,
+    ) -> Addr<RedisSubscriber> {
+        let options = self
+            .connection
+            .connection_builder()
+            .connect_options()
+            .clone();
+        let unsubscribed = ctx.address().recipient();
+        self.subscribers
+            .entry(addr.clone())
+            .or_insert_with(|| RedisSubscriber::start_node(addr, options, unsubscribed))
+            .clone()
+    }
+
+    /// Get the address of the master node serving the slot
+    fn master_of(&self, slot: u16) -> Option<String> {
+        self.slots
+            .iter()
+            .find(|slots| slots.start <= slot && slot <= slots.end)
+            .map(Slots::master)
+    }
+
+    /// Get the address of the node serving a read-only command on the slot
+    /// following `ReadPolicy`, and whether it is a replica
+    fn reader_of(&mut self, slot: u16) -> Option<(String, bool)> {
+        let slots = self
+            .slots
+            .iter()
+            .find(|slots| slots.start <= slot && slot <= slots.end)?;
+        let master = slots.master();
+        let replicas: Vec<String> = slots.replicas().collect();
+
+        let replica = match self.read_policy {
+            ReadPolicy::Master => None,
+            ReadPolicy::PreferReplica => replicas.into_iter().next(),
+            ReadPolicy::RoundRobinReplicas if replicas.is_empty() => None,
+            ReadPolicy::RoundRobinReplicas => {
+                self.next_replica = self.next_replica.wrapping_add(1);
+                Some(replicas[self.next_replica % replicas.len()].clone())
+            }
+            ReadPolicy::Nearest => {
+                let latencies = &self.latencies;
+                let latency = |addr: &String| latencies.get(addr).cloned();
+                replicas
+                    .into_iter()
+                    .filter_map(|addr| latency(&addr).map(|latency| (latency, addr)))
+                    .min()
+                    .filter(|&(replica, _)| match latency(&master) {
+                        Some(master) => replica < master,
+                        None => true,
+                    })
+                    .map(|(_, addr)| addr)
+            }
+        };
+        Some(match replica {
+            Some(replica) => (replica, true),
+            None => (master, false),
+        })
+    }
+
+    /// Measure the `PING` latency of every node for `ReadPolicy::Nearest`
+    fn measure_latencies(&mut self, ctx: &mut Context<Self>) {
+        let mut nodes = vec![];
+        for slots in self.slots.iter() {
+            nodes.push((slots.master(), false));
+            nodes.extend(slots.replicas().map(|addr| (addr, true)));
+        }
+
+        for (addr, replica) in nodes {
+            let connection = if replica {
+                self.replica_connection(addr.clone())
+            } else {
+                self.connection(addr.clone())
+            };
+            let start = Instant::now();
+            let ping = connection.send(Ping(None));
+            ctx.spawn(ping.into_actor(self).map(move |res, this, _ctx| match res {
+                Ok(Ok(_)) => {
+                    this.latencies.insert(addr, start.elapsed());
+                }
+                res => {
+                    warn!("measuring latency of {} failed: {:?}", addr, res);
+                    this.latencies.remove(&addr);
+                }
+            }));
+        }
+    }
+
+    /// Subscribe to shard `channels` of a single slot on the node at `addr`
+    fn ssubscribe(
+        &mut self,
+        addr: String,
+        channels: Vec<String>,
+        listener: Listener<PubSubMessage>,
+        retry: usize,
+        ctx: &mut Context<Self>,
+    ) -> ResponseActFuture<Self, Result<(), Error>> {
+        let msg = SSubscribe {
+            channels: channels.clone(),
+            listener: listener.clone(),
+        };
+        Box::new(self.subscriber(addr.clone(), ctx).send(msg).into_actor(self).then(
+            move |res, this, ctx| -> ResponseActFuture<Self, Result<(), Error>> {
+                match res {
+                    Ok(Ok(())) => {
+                        for channel in channels {
+                            let subscription = this
+                                .shard_channels
+                                .entry(channel)
+                                .or_insert_with(|| ShardSubscription {
+                                    node: addr.clone(),
+                                    listeners: vec![],
+                                });
+                            subscription.node = addr.clone();
+                            subscription.listeners.push(listener.clone());
+                        }
+                        Box::new(actix::fut::ok(()))
+                    }
+                    Ok(Err(Error::Redis(RespError::Remote(ref e))))
+                        if e.starts_with("MOVED") && retry < MAX_RETRY =>
+                    {
+                        info!("MOVED redirection of SSUBSCRIBE: retry = {}", retry);
+
+                        let addr = e.split(' ').nth(2).unwrap_or_default().to_string();
+                        this.request_refresh(ctx);
+
+                        this.ssubscribe(addr, channels, listener, retry + 1, ctx)
+                    }
+                    Ok(Err(e)) => Box::new(actix::fut::err(e)),
+                    Err(_canceled) => Box::new(actix::fut::err(Error::Disconnected)),
+                }
+            },
+        ))
+    }
+
+    /// Send the entries of a pipeline to the nodes they are grouped by,
+    /// following MOVED and ASK redirections of each entry
+    fn pipeline(
+        &mut self,
+        batches: HashMap<String, Vec<PipelineEntry>>,
+        retry: usize,
+    ) -> ResponseActFuture<Self, Result<Vec<(usize, RespValue)>, Error>> {
+        let sent: Vec<_> = batches
+            .into_iter()
+            .map(|(addr, entries)| {
+                // ASKING and the request go together over the same connection
+                let mut pipeline = Pipeline::new();
+                for entry in entries.iter() {
+                    if entry.asking {
+                        pipeline.add(Asking);
+                    }
+                    pipeline.add_raw(entry.request.clone());
+                }
+                self.connection(addr)
+                    .send(pipeline)
+                    .map(move |res| (entries, res))
+            })
+            .collect();
 
         Box::new(
-            control_connection
-                .send(ClusterSlots)
-                .map(|res| match res {
-                    Ok(Ok(slots)) => Ok(slots),
-                    Ok(Err(e)) => Err(e),
-                    Err(_) => Err(Error::Disconnected),
-                })
+            future::join_all(sent).into_actor(self).then(
+                move |results,
+                      this,
+                      ctx|
+                      -> ResponseActFuture<
+                    Self,
+                    Result<Vec<(usize, RespValue)>, Error>,
+                > {
+                    let mut replies = vec![];
+                    let mut redirected: HashMap<String, Vec<PipelineEntry>> =
+                        HashMap::new();
+                    let mut moved = false;
+
+                    for (entries, res) in results {
+                        let mut res = match res {
+                            Ok(Ok(res)) => res.into_inner().into_iter(),
+                            Ok(Err(e)) => return Box::new(actix::fut::err(e)),
+                            Err(_canceled) => {
+                                return Box::new(actix::fut::err(Error::Disconnected))
+                            }
+                        };
+
+                        for entry in entries {
+                            if entry.asking {
+                                let asked = match res.next() {
+                                    Some(asked) => asked,
+                                    None => {
+                                        return Box::new(actix::fut::err(
+                                            Error::Disconnected,
+                                        ))
+                                    }
+                                };
+                                if let Err(e) = Asking::from_response(asked) {
+                                    // the request was not let in the importing
+                                    // slot, so its reply is not the one asked
+                                    res.next();
+                                    let e = match e {
+                                        RespError::RESP(
+                                            _,
+                                            Some(RespValue::Error(e)),
+                                        ) => e,
+                                        e => e.to_string(),
+                                    };
+                                    replies.push((entry.index, RespValue::Error(e)));
+                                    continue;
+                                }
+                            }
+                            match res.next() {
+                                Some(RespValue::Error(ref e))
+                                    if (e.starts_with("MOVED")
+                                        || e.starts_with("ASK"))
+                                        && retry < MAX_RETRY =>
+                                {
+                                    let asking = e.starts_with("ASK");
+                                    moved |= !asking;
+                                    let addr = e
+                                        .split(' ')
+                                        .nth(2)
+                                        .unwrap_or_default()
+                                        .to_string();
+                                    redirected
+                                        .entry(addr)
+                                        .or_default()
+                                        .push(PipelineEntry { asking, ..entry });
+                                }
+                                Some(res) => replies.push((entry.index, res)),
+                                None => {
+                                    return Box::new(actix::fut::err(
+                                        Error::Disconnected,
+                                    ))
+                                }
+                            }
+                        }
+                    }
+
+                    if redirected.is_empty() {
+                        return Box::new(actix::fut::ok(replies));
+                    }
+
+                    info!("redirection of pipeline: retry = {}", retry);
+                    if moved {
+                        this.request_refresh(ctx);
+                    }
+
+                    Box::new(this.pipeline(redirected, retry + 1).map(
+                        move |res, _this, _ctx| {
+                            res.map(|mut rest| {
+                                replies.append(&mut rest);
+                                replies
+                            })
+                        },
+                    ))
+                },
+            ),
+        )
+    }
+
+    /// Run `transaction` on the node at `addr`, following MOVED redirections
+    fn transaction(
+        &mut self,
+        addr: String,
+        transaction: Transaction,
+        retry: usize,
+    ) -> ResponseActFuture<Self, Result<Option<Replies>, Error>> {
+        Box::new(
+            self.connection(addr)
+                .send(transaction.clone())
                 .into_actor(self)
-                .map(|res, this, _ctx| match res {
-                    Ok(slots) => {
-                        for slots in slots.iter() {
-                            this.connections
-                                .entry(slots.master().to_string())
-                                .or_insert_with(|| {
-                                    RedisActor::start(slots.master().clone())
-                                });
+                .then(
+                    move |res,
+                          this,
+                          ctx|
+                          -> ResponseActFuture<Self, Result<Option<Replies>, Error>> {
+                        match res {
+                            Ok(Err(Error::Redis(RespError::Remote(ref e))))
+                                if e.starts_with("MOVED") && retry < MAX_RETRY =>
+                            {
+                                info!(
+                                    "MOVED redirection of transaction: retry = {}",
+                                    retry
+                                );
+
+                                let addr =
+                                    e.split(' ').nth(2).unwrap_or_default().to_string();
+                                this.request_refresh(ctx);
+
+                                this.transaction(addr, transaction, retry + 1)
+                            }
+                            Ok(res) => Box::new(actix::fut::result(res)),
+                            Err(_canceled) => {
+                                Box::new(actix::fut::err(Error::Disconnected))
+                            }
                         }
-                        this.slots = slots;
-                        debug!("slots: {:?}", this.slots);
+                    },
+                ),
+        )
+    }
+
+    /// Run `cas` over a new connection to the node at `addr`, following
+    /// MOVED and ASK redirections
+    fn check_and_set(
+        &mut self,
+        addr: String,
+        mut cas: CheckAndSet,
+        retry: usize,
+        asking: bool,
+    ) -> ResponseActFuture<Self, Result<Option<Replies>, Error>> {
+        if self.shutdown {
+            return Box::new(actix::fut::err(Error::ShuttingDown));
+        }
+
+        let builder = self.connection.connection_builder();
+        let options = builder.connect_options().clone();
+        let timeout = builder.get_response_timeout();
+        let res = async move {
+            let res =
+                transaction::check_and_set(addr, options, timeout, &mut cas, asking)
+                    .await;
+            (cas, res)
+        };
+        Box::new(res.into_actor(self).then(
+            move |(cas, res),
+                  this,
+                  ctx|
+                  -> ResponseActFuture<Self, Result<Option<Replies>, Error>> {
+                match res {
+                    Err(Error::Redis(RespError::Remote(ref e)))
+                        if (e.starts_with("MOVED") || e.starts_with("ASK"))
+                            && retry < MAX_RETRY =>
+                    {
+                        info!("redirection of check-and-set: retry = {}", retry);
+
+                        let asking = e.starts_with("ASK");
+                        if !asking {
+                            this.request_refresh(ctx);
+                        }
+                        let addr = e.split(' ').nth(2).unwrap_or_default().to_string();
+                        this.check_and_set(addr, cas, retry + 1, asking)
                     }
-                    Err(e) => {
-                        warn!("refreshing slots failed: {:?}", e);
+                    res => Box::new(actix::fut::result(res)),
+                }
+            },
+        ))
+    }
+
+    /// Subscribe `listeners` to the shard `channel` on the owner of its slot
+    fn resubscribe(
+        &mut self,
+        channel: String,
+        listeners: Vec<Listener<PubSubMessage>>,
+        ctx: &mut Context<Self>,
+    ) {
+        let slot = hash_slot(channel.as_bytes());
+        let addr = match self.master_of(slot) {
+            Some(addr) => addr,
+            None => {
+                warn!("no node is serving the slot {}", slot);
+                return;
+            }
+        };
+
+        for listener in listeners {
+            let fut =
+                self.ssubscribe(addr.clone(), vec![channel.clone()], listener, 0, ctx);
+            ctx.spawn(fut.map(|res, _this, _ctx| {
+                if let Err(e) = res {
+                    warn!("resubscribing shard channel failed: {:?}", e);
+                }
+            }));
+        }
+    }
+
+    /// Move the subscriptions of the migrated slots to their new owners
+    fn move_shard_channels(&mut self, ctx: &mut Context<Self>) {
+        let moved: Vec<String> = self
+            .shard_channels
+            .iter()
+            .filter(|(channel, subscription)| {
+                match self.master_of(hash_slot(channel.as_bytes())) {
+                    Some(addr) => addr != subscription.node,
+                    None => false,
+                }
+            })
+            .map(|(channel, _)| channel.clone())
+            .collect();
+
+        for channel in moved {
+            if let Some(subscription) = self.shard_channels.remove(&channel) {
+                info!("shard channel {} moved from {}", channel, subscription.node);
+                if let Some(subscriber) = self.subscribers.get(&subscription.node) {
+                    subscriber.do_send(SUnsubscribe {
+                        channels: vec![channel.clone()],
+                    });
+                }
+                self.resubscribe(channel, subscription.listeners, ctx);
+            }
+        }
+    }
+
+    /// Make the keyspace subscriptions on the masters which joined the cluster
+    fn subscribe_keyspace_masters(&mut self, ctx: &mut Context<Self>) {
+        let masters: HashSet<String> = self.slots.iter().map(Slots::master).collect();
+        for master in masters {
+            if self.keyspace_nodes.contains(&master) {
+                continue;
+            }
+            let subscriber = self.subscriber(master.clone(), ctx);
+            for msg in self.keyspace.iter() {
+                let req = subscriber.send(msg.clone());
+                ctx.spawn(req.into_actor(self).map(|res, _this, _ctx| match res {
+                    Ok(Ok(())) => (),
+                    res => warn!("subscribing keyspace events failed: {:?}", res),
+                }));
+            }
+            self.keyspace_nodes.insert(master);
+        }
+    }
+
+    /// Ask the nodes for the slots in turn until one of them answers,
+    /// returning its address along with the slots. The nodes there are no
+    /// connections to, like the seeds, are asked over a connection of their
+    /// own, so none is kept to the nodes which are down or not in the cluster.
+    fn cluster_slots(
+        &mut self,
+        mut nodes: VecDeque<String>,
+    ) -> ResponseActFuture<Self, Result<(String, Vec<Slots>), Error>> {
+        let addr = match nodes.pop_front() {
+            Some(addr) => addr,
+            None => return Box::new(actix::fut::err(Error::NotConnected)),
+        };
+
+        let slots = match self.connections.get(&addr) {
+            Some(pool) => pool
+                .send(ClusterSlots)
+                .map(|res| res.unwrap_or(Err(Error::Disconnected)))
+                .boxed_local(),
+            None => {
+                let builder = self.connection.connection_builder();
+                let options = builder.connect_options().clone();
+                let slots = redis::command(addr.clone(), options, ClusterSlots);
+                match builder.get_response_timeout() {
+                    Some(timeout) => tokio::time::timeout(timeout, slots)
+                        .map(|res| res.unwrap_or(Err(Error::Timeout)))
+                        .boxed_local(),
+                    None => slots.boxed_local(),
+                }
+            }
+        };
+
+        Box::new(
+            slots.into_actor(self).then(
+                move |res,
+                      this,
+                      _ctx|
+                      -> ResponseActFuture<
+                    Self,
+                    Result<(String, Vec<Slots>), Error>,
+                > {
+                    match res {
+                        Ok(slots) => Box::new(actix::fut::ok((addr, slots))),
+                        Err(e) => {
+                            warn!("node {} can not tell the slots: {:?}", addr, e);
+                            this.cluster_slots(nodes)
+                        }
                     }
-                }),
+                },
+            ),
         )
     }
+
+    /// Shut down the connections to the nodes which are not in the slots any
+    /// more, except the one serving the commands without keys
+    fn retire_nodes(&mut self, ctx: &mut Context<Self>) {
+        let masters: HashSet<String> = self.slots.iter().map(Slots::master).collect();
+        let replicas: HashSet<String> =
+            self.slots.iter().flat_map(Slots::replicas).collect();
+
+        let control_addr = &self.control_addr;
+        let masters_left: Vec<String> = self
+            .connections
+            .keys()
+            .filter(|&addr| !masters.contains(addr) && addr != control_addr)
+            .cloned()
+            .collect();
+        let replicas_left: Vec<String> = self
+            .replicas
+            .keys()
+            .filter(|&addr| !replicas.contains(addr))
+            .cloned()
+            .collect();
+
+        let mut retired = vec![];
+        for addr in masters_left {
+            retired.extend(self.connections.remove(&addr).map(|pool| (addr, pool)));
+        }
+        for addr in replicas_left {
+            retired.extend(self.replicas.remove(&addr).map(|pool| (addr, pool)));
+        }
+        self.latencies
+            .retain(|addr, _| masters.contains(addr) || replicas.contains(addr));
+
+        for (addr, pool) in retired {
+            info!("closing the connections to {}", addr);
+            ctx.spawn(pool.shutdown(RETIRE_TIMEOUT).into_actor(self));
+        }
+    }
+
+    /// Refresh the slots in the background. Requests made while a refresh is
+    /// in flight are served by it, and refreshes are at least
+    /// `min_refresh_interval` apart. Commands keep being routed by the
+    /// current slots meanwhile.
+    fn request_refresh(&mut self, ctx: &mut Context<Self>) {
+        if self.refreshing || self.refresh_scheduled {
+            return;
+        }
+
+        let elapsed = self.last_refresh.map(|last| last.elapsed());
+        match elapsed {
+            Some(elapsed) if elapsed < self.min_refresh_interval => {
+                self.refresh_scheduled = true;
+                ctx.run_later(self.min_refresh_interval - elapsed, |this, ctx| {
+                    this.refresh_scheduled = false;
+                    this.request_refresh(ctx);
+                });
+            }
+            _ => {
+                let fut = self.start_refresh();
+                ctx.spawn(fut);
+            }
+        }
+    }
+
+    /// Wait for the next refresh of the slots, requesting it
+    fn next_refresh(&mut self, ctx: &mut Context<Self>) -> oneshot::Receiver<()> {
+        let (tx, rx) = oneshot::channel();
+        self.refresh_waiters.push(tx);
+        self.request_refresh(ctx);
+        rx
+    }
+
+    fn start_refresh(&mut self) -> ResponseActFuture<Self, ()> {
+        self.refreshing = true;
+        self.last_refresh = Some(Instant::now());
+        Box::new(self.refresh_slots().map(|(), this, _ctx| {
+            this.refreshing = false;
+            for tx in this.refresh_waiters.drain(..) {
+                let _ = tx.send(());
+            }
+        }))
+    }
+
+    fn refresh_slots(&mut self) -> ResponseActFuture<Self, ()> {
+        // the last node which answered, the seeds, then the other known nodes
+        let mut nodes = VecDeque::new();
+        let known = self
+            .slots
+            .iter()
+            .flat_map(|slots| std::iter::once(slots.master()).chain(slots.replicas()));
+        for addr in std::iter::once(self.control_addr.clone())
+            .chain(self.seeds.iter().cloned())
+            .chain(known)
+        {
+            if !nodes.contains(&addr) {
+                nodes.push_back(addr);
+            }
+        }
+
+        Box::new(self.cluster_slots(nodes).map(|res, this, ctx| match res {
+            Ok((addr, slots)) => {
+                this.control_addr = addr;
+                for slots in slots.iter() {
+                    this.connection(slots.master());
+                }
+                this.slots = slots;
+                debug!("slots: {:?}", this.slots);
+                this.retire_nodes(ctx);
+                if this.read_policy == ReadPolicy::Nearest {
+                    this.measure_latencies(ctx);
+                }
+                this.move_shard_channels(ctx);
+                this.subscribe_keyspace_masters(ctx);
+            }
+            Err(e) => {
+                warn!("refreshing slots failed: {:?}", e);
+            }
+        }))
+    }
+}
+
+/// Builder of `RedisClusterActor`
+#[derive(Debug, Clone)]
+pub struct RedisClusterActorBuilder {
+    connection: PoolBuilder,
+    read_policy: ReadPolicy,
+    refresh_interval: Option<Duration>,
+    min_refresh_interval: Duration,
+}
+
+impl Default for RedisClusterActorBuilder {
+    fn default() -> Self {
+        RedisClusterActorBuilder {
+            connection: PoolBuilder::default(),
+            read_policy: ReadPolicy::Master,
+            refresh_interval: None,
+            min_refresh_interval: MIN_REFRESH_INTERVAL,
+        }
+    }
+}
+
+impl RedisClusterActorBuilder {
+    /// Configure the connections to every node of the cluster
+    pub fn connection(mut self, builder: RedisActorBuilder) -> Self {
+        self.connection = self.connection.connection(builder);
+        self
+    }
+
+    /// Set the number of connections to each node. Default is 1.
+    pub fn pool_size(mut self, size: usize) -> Self {
+        self.connection = self.connection.size(size);
+        self
+    }
+
+    /// Set how the connection to a node is picked for each request.
+    /// Default is round-robin.
+    pub fn pool_selection(mut self, selection: Selection) -> Self {
+        self.connection = self.connection.selection(selection);
+        self
+    }
+
+    /// Set which node serves the read-only commands, see
+    /// `Command::is_read_only`. Default is the master.
+    pub fn read_policy(mut self, policy: ReadPolicy) -> Self {
+        self.read_policy = policy;
+        self
+    }
+
+    /// Refresh the slots every `interval` in addition to the refreshes
+    /// triggered by MOVED redirections
+    pub fn refresh_interval(mut self, interval: Duration) -> Self {
+        self.refresh_interval = Some(interval);
+        self
+    }
+
+    /// Set the minimum time between two refreshes of the slots, so a burst of
+    /// MOVED redirections during resharding triggers few of them.
+    /// Default is 1 second.
+    pub fn min_refresh_interval(mut self, interval: Duration) -> Self {
+        self.min_refresh_interval = interval;
+        self
+    }
+
+    /// Start new `Supervisor` with `RedisClusterActor`.
+    pub fn start<S: Into<String>>(&self, addr: S) -> Addr<RedisClusterActor> {
+        self.start_seeds(vec![addr.into()])
+    }
+
+    /// Start new `Supervisor` with `RedisClusterActor` discovering the
+    /// cluster from any of the `seeds`, tried in order.
+    ///
+    /// # Panics
+    /// Panics if `seeds` is empty.
+    pub fn start_seeds<I>(&self, seeds: I) -> Addr<RedisClusterActor>
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let seeds: Vec<String> = seeds.into_iter().map(Into::into).collect();
+        assert!(!seeds.is_empty(), "no seed node of the cluster");
+        let connection = self.connection.clone();
+        let read_policy = self.read_policy;
+        let refresh_interval = self.refresh_interval;
+        let min_refresh_interval = self.min_refresh_interval;
+
+        let mut options = connection.connection_builder().connect_options().clone();
+        options.readonly = true;
+        let replica_connection = connection
+            .clone()
+            .connection(connection.connection_builder().clone().options(options));
+
+        Supervisor::start(move |_ctx| RedisClusterActor {
+            control_addr: seeds[0].clone(),
+            seeds: seeds.clone(),
+            connection,
+            slots: vec![],
+            connections: HashMap::new(),
+            read_policy,
+            replica_connection: replica_connection.clone(),
+            replicas: HashMap::new(),
+            next_replica: 0,
+            latencies: HashMap::new(),
+            refresh_interval,
+            min_refresh_interval,
+            last_refresh: None,
+            refreshing: false,
+            refresh_scheduled: false,
+            refresh_waiters: vec![],
+            subscribers: HashMap::new(),
+            shard_channels: HashMap::new(),
+            keyspace: vec![],
+            keyspace_nodes: HashSet::new(),
+            shutdown: false,
+        })
+    }
 }
 
 impl Actor for RedisClusterActor {
     type Context = Context<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
+        // restarted by the supervisor after `Shutdown`
+        if self.shutdown {
+            return;
+        }
+
         // TODO: does this wait prevent the issue (#1?)?
-        ctx.wait(self.refresh_slots());
+        // there are no slots to route the commands by until the first refresh
+        ctx.wait(self.start_refresh());
+
+        if let Some(interval) = self.refresh_interval {
+            ctx.run_interval(interval, |this, ctx| this.request_refresh(ctx));
+        }
     }
 }
 
@@ -90,6 +884,13 @@ impl Supervised for RedisClusterActor {
     fn restarting(&mut self, _: &mut Self::Context) {
         self.slots.clear();
         self.connections.clear();
+        self.replicas.clear();
+        self.latencies.clear();
+        // the timers and futures of the refreshes are gone
+        self.refreshing = false;
+        self.refresh_scheduled = false;
+        self.refresh_waiters.clear();
+        // subscribers keep their subscriptions over the restart
     }
 }
 
@@ -98,6 +899,12 @@ struct Retry {
     addr: String,
     req: RespValue,
     retry: usize,
+    /// Blocking timeout of a blocking command
+    blocking: Option<Duration>,
+    /// Whether `addr` is a replica serving a read-only command
+    replica: bool,
+    /// Whether to precede the request with `ASKING` after an ASK redirection
+    asking: bool,
 }
 
 impl Message for Retry {
@@ -106,7 +913,29 @@ impl Message for Retry {
 
 impl Retry {
     fn new(addr: String, req: RespValue, retry: usize) -> Self {
-        Retry { addr, req, retry }
+        Retry {
+            addr,
+            req,
+            retry,
+            blocking: None,
+            replica: false,
+            asking: false,
+        }
+    }
+
+    fn blocking(mut self, blocking: Option<Duration>) -> Self {
+        self.blocking = blocking;
+        self
+    }
+
+    fn replica(mut self, replica: bool) -> Self {
+        self.replica = replica;
+        self
+    }
+
+    fn asking(mut self, asking: bool) -> Self {
+        self.asking = asking;
+        self
     }
 }
 
@@ -116,12 +945,22 @@ impl Handler<Retry> for RedisClusterActor {
     fn handle(&mut self, msg: Retry, _ctx: &mut Self::Context) -> Self::Result {
         fn do_retry(
             this: &mut RedisClusterActor,
-            addr: String,
-            req: RespValue,
-            retry: usize,
+            msg: Retry,
         ) -> ResponseActFuture<RedisClusterActor, Result<RespValue, Error>> {
             use actix::fut::{err, ok};
 
+            if this.shutdown {
+                return Box::new(err(Error::ShuttingDown));
+            }
+
+            let Retry {
+                addr,
+                req,
+                retry,
+                blocking,
+                replica,
+                asking,
+            } = msg;
             debug!(
                 "processing: req = {}, addr = {}, retry = {}",
                 fmt_resp_value(&req),
@@ -129,84 +968,98 @@ impl Handler<Retry> for RedisClusterActor {
                 retry
             );
 
-            let connection = this
-                .connections
-                .entry(addr.clone())
-                .or_insert_with(move || RedisActor::start(addr));
-            Box::new(
-                connection
+            let connection = if replica {
+                this.replica_connection(addr)
+            } else {
+                this.connection(addr)
+            };
+            let sent = match blocking {
+                Some(timeout) => connection
+                    .send(crate::redis::Blocking {
+                        request: req.clone(),
+                        timeout,
+                        asking,
+                    })
+                    .boxed_local(),
+                // ASKING only applies to the next command over the connection
+                None if asking => {
+                    let mut pipeline = Pipeline::new();
+                    pipeline.add(Asking);
+                    pipeline.add_raw(req.clone());
+                    connection
+                        .send(pipeline)
+                        .map(|res| res.map(|res| res.and_then(asked)))
+                        .boxed_local()
+                }
+                None => connection
                     .send(crate::redis::Command(req.clone()))
-                    .into_actor(this)
-                    .then(move |res, this, ctx| {
-                        debug!(
-                            "received: {:?}",
-                            res.as_ref().map(|res| res.as_ref().map(fmt_resp_value))
+                    .boxed_local(),
+            };
+            Box::new(sent.into_actor(this).then(move |res, this, ctx| {
+                debug!(
+                    "received: {:?}",
+                    res.as_ref().map(|res| res.as_ref().map(fmt_resp_value))
+                );
+                match res {
+                    Ok(Ok(RespValue::Error(ref e)))
+                        if e.starts_with("MOVED") && retry < MAX_RETRY =>
+                    {
+                        info!(
+                            "MOVED redirection: retry = {}, request = {}",
+                            retry,
+                            fmt_resp_value(&req)
                         );
-                        match res {
-                            Ok(Ok(RespValue::Error(ref e)))
-                                if e.starts_with("MOVED") && retry < MAX_RETRY =>
-                            {
-                                info!(
-                                    "MOVED redirection: retry = {}, request = {}",
-                                    retry,
-                                    fmt_resp_value(&req)
-                                );
 
-                                let mut values = e.split(' ');
-                                let _moved = values.next().unwrap();
-                                let _slot = values.next().unwrap();
-                                let addr = values.next().unwrap();
+                        let mut values = e.split(' ');
+                        let _moved = values.next().unwrap();
+                        let _slot = values.next().unwrap();
+                        let addr = values.next().unwrap();
 
-                                ctx.wait(this.refresh_slots());
+                        this.request_refresh(ctx);
 
-                                do_retry(this, addr.to_string(), req, retry + 1)
-                            }
-                            Ok(Ok(RespValue::Error(ref e)))
-                                if e.starts_with("ASK") && retry < MAX_RETRY =>
-                            {
-                                info!(
-                                    "ASK redirection: retry = {}, request = {}",
-                                    retry,
-                                    fmt_resp_value(&req)
-                                );
+                        let msg = Retry::new(addr.to_string(), req, retry + 1)
+                            .blocking(blocking);
+                        do_retry(this, msg)
+                    }
+                    Ok(Ok(RespValue::Error(ref e)))
+                        if e.starts_with("ASK") && retry < MAX_RETRY =>
+                    {
+                        info!(
+                            "ASK redirection: retry = {}, request = {}",
+                            retry,
+                            fmt_resp_value(&req)
+                        );
 
-                                let mut values = e.split(' ');
-                                let _moved = values.next().unwrap();
-                                let _slot = values.next().unwrap();
-                                let addr = values.next().unwrap();
-
-                                ctx.spawn(
-                                    // No retry for ASKING
-                                    do_retry(
-                                        this,
-                                        addr.to_string(),
-                                        Asking.into_request(),
-                                        MAX_RETRY,
-                                    )
-                                    .map(
-                                        |res, _this, _ctx| {
-                                            match res.map(Asking::from_response) {
-                                                Ok(Ok(())) => {}
-                                                e => warn!(
-                                                    "failed to issue ASKING: {:?}",
-                                                    e
-                                                ),
-                                            };
-                                        },
-                                    ),
-                                );
+                        let mut values = e.split(' ');
+                        let _moved = values.next().unwrap();
+                        let _slot = values.next().unwrap();
+                        let addr = values.next().unwrap();
 
-                                do_retry(this, addr.to_string(), req, retry + 1)
-                            }
-                            Ok(Ok(res)) => Box::new(ok(res)),
-                            Ok(Err(e)) => Box::new(err(e)),
-                            Err(_canceled) => Box::new(err(Error::Disconnected)),
-                        }
-                    }),
-            )
+                        let msg = Retry::new(addr.to_string(), req, retry + 1)
+                            .blocking(blocking)
+                            .asking(true);
+                        do_retry(this, msg)
+                    }
+                    Ok(Ok(res)) => Box::new(ok(res)),
+                    Ok(Err(e)) => Box::new(err(e)),
+                    Err(_canceled) => Box::new(err(Error::Disconnected)),
+                }
+            }))
         }
 
-        do_retry(self, msg.addr, msg.req, msg.retry)
+        do_retry(self, msg)
+    }
+}
+
+/// Reply of a request sent after `ASKING`, failing if `ASKING` did
+fn asked(replies: Replies) -> Result<RespValue, Error> {
+    let mut replies = replies.into_inner().into_iter();
+    match (replies.next(), replies.next()) {
+        (Some(asking), Some(res)) => {
+            Asking::from_response(asking).map_err(Error::Redis)?;
+            Ok(res)
+        }
+        _ => Err(Error::Disconnected),
     }
 }
 
@@ -218,27 +1071,39 @@ where
         + 'static,
     <M as Command>::Output: Send + 'static,
 {
-    type Result = ResponseActFuture<RedisClusterActor, Result<M::Output, Error>>;
+    type Result = Cancelable<RedisClusterActor, Result<M::Output, Error>>;
 
     fn handle(&mut self, msg: M, ctx: &mut Self::Context) -> Self::Result {
+        if self.shutdown {
+            let res = actix::fut::err(Error::ShuttingDown);
+            return Cancelable::actor_future(Box::new(res), false);
+        }
+
         // refuse operations over multiple slots
         let slot = match msg.key_slot() {
             Ok(slot) => slot,
-            Err(e) => return Box::new(actix::fut::err(Error::MultipleSlot(e))),
+            Err(e) => {
+                let res = actix::fut::err(Error::MultipleSlot(e));
+                return Cancelable::actor_future(Box::new(res), false);
+            }
         };
+        let blocking = msg.blocking_timeout();
+        let read_only = msg.is_read_only();
         let req = msg.into_request();
 
         let fut = (|| match slot {
             Some(slot) => {
-                for slots in self.slots.iter() {
-                    if slots.start <= slot && slot <= slots.end {
-                        let addr = slots.master().to_string();
-                        return actix::Handler::handle(
-                            self,
-                            Retry::new(addr, req, 0),
-                            ctx,
-                        );
-                    }
+                let node = if read_only {
+                    self.reader_of(slot)
+                } else {
+                    self.master_of(slot).map(|addr| (addr, false))
+                };
+                if let Some((addr, replica)) = node {
+                    return actix::Handler::handle(
+                        self,
+                        Retry::new(addr, req, 0).blocking(blocking).replica(replica),
+                        ctx,
+                    );
                 }
 
                 warn!("no node is serving the slot {}", slot);
@@ -246,18 +1111,244 @@ where
             }
             None => actix::Handler::handle(
                 self,
-                Retry::new(self.initial_addr.clone(), req, 0),
+                Retry::new(self.control_addr.clone(), req, 0).blocking(blocking),
                 ctx,
             ),
         })();
 
-        Box::new(fut.map(|res, _this, _ctx| match res {
+        let res = fut.map(|res, _this, _ctx| match res {
             Ok(res) => M::from_response(res).map_err(Error::Redis),
             Err(e) => Err(e),
+        });
+        Cancelable::actor_future(Box::new(res), blocking.is_some())
+    }
+}
+
+impl Handler<Transaction> for RedisClusterActor {
+    type Result = ResponseActFuture<RedisClusterActor, Result<Option<Replies>, Error>>;
+
+    fn handle(&mut self, msg: Transaction, _ctx: &mut Self::Context) -> Self::Result {
+        if self.shutdown {
+            return Box::new(actix::fut::err(Error::ShuttingDown));
+        }
+
+        // refuse transactions over multiple slots
+        let addr = match msg.key_slot() {
+            Ok(Some(slot)) => match self.master_of(slot) {
+                Some(addr) => addr,
+                None => {
+                    warn!("no node is serving the slot {}", slot);
+                    return Box::new(actix::fut::err(Error::NotConnected));
+                }
+            },
+            Ok(None) => self.control_addr.clone(),
+            Err(e) => return Box::new(actix::fut::err(Error::MultipleSlot(e))),
+        };
+
+        self.transaction(addr, msg, 0)
+    }
+}
+
+impl Handler<Pipeline> for RedisClusterActor {
+    type Result = ResponseActFuture<RedisClusterActor, Result<Replies, Error>>;
+
+    fn handle(&mut self, msg: Pipeline, _ctx: &mut Self::Context) -> Self::Result {
+        if self.shutdown {
+            return Box::new(actix::fut::err(Error::ShuttingDown));
+        }
+
+        // one sub-pipeline per node
+        let mut batches: HashMap<String, Vec<PipelineEntry>> = HashMap::new();
+        for (index, entry) in msg.into_entries().into_iter().enumerate() {
+            let addr = match entry.slot {
+                Ok(Some(slot)) => match self.master_of(slot) {
+                    Some(addr) => addr,
+                    None => {
+                        warn!("no node is serving the slot {}", slot);
+                        return Box::new(actix::fut::err(Error::NotConnected));
+                    }
+                },
+                Ok(None) => self.control_addr.clone(),
+                Err(e) => return Box::new(actix::fut::err(Error::MultipleSlot(e))),
+            };
+            batches.entry(addr).or_default().push(PipelineEntry {
+                index,
+                request: entry.request,
+                asking: false,
+            });
+        }
+
+        Box::new(self.pipeline(batches, 0).map(|res, _this, _ctx| {
+            let mut replies = res?;
+            replies.sort_by_key(|&(index, _)| index);
+            let replies = replies.into_iter().map(|(_, res)| res);
+            Ok(Replies::new(replies.collect()))
         }))
     }
 }
 
+impl Handler<CheckAndSet> for RedisClusterActor {
+    type Result = ResponseActFuture<Self, Result<Option<Replies>, Error>>;
+
+    fn handle(&mut self, msg: CheckAndSet, _ctx: &mut Self::Context) -> Self::Result {
+        // the watched keys and the transaction have to be on the same node
+        let addr = match msg.key_slot() {
+            Ok(Some(slot)) => match self.master_of(slot) {
+                Some(addr) => addr,
+                None => {
+                    warn!("no node is serving the slot {}", slot);
+                    return Box::new(actix::fut::err(Error::NotConnected));
+                }
+            },
+            Ok(None) => self.control_addr.clone(),
+            Err(e) => return Box::new(actix::fut::err(Error::MultipleSlot(e))),
+        };
+
+        self.check_and_set(addr, msg, 0, false)
+    }
+}
+
+impl Handler<SSubscribe> for RedisClusterActor {
+    type Result = ResponseActFuture<RedisClusterActor, Result<(), Error>>;
+
+    fn handle(&mut self, msg: SSubscribe, _ctx: &mut Self::Context) -> Self::Result {
+        // a single SSUBSCRIBE can not span slots
+        let mut slots: BTreeMap<u16, Vec<String>> = BTreeMap::new();
+        for channel in msg.channels {
+            slots
+                .entry(hash_slot(channel.as_bytes()))
+                .or_default()
+                .push(channel);
+        }
+
+        let mut fut: Self::Result = Box::new(actix::fut::ok(()));
+        for (slot, channels) in slots {
+            let listener = msg.listener.clone();
+            fut = Box::new(fut.then(move |res, this, ctx| -> Self::Result {
+                if res.is_err() {
+                    return Box::new(actix::fut::result(res));
+                }
+                match this.master_of(slot) {
+                    Some(addr) => this.ssubscribe(addr, channels, listener, 0, ctx),
+                    None => {
+                        warn!("no node is serving the slot {}", slot);
+                        Box::new(actix::fut::err(Error::NotConnected))
+                    }
+                }
+            }));
+        }
+        fut
+    }
+}
+
+impl Handler<SUnsubscribe> for RedisClusterActor {
+    type Result = ResponseFuture<Result<(), Error>>;
+
+    fn handle(&mut self, msg: SUnsubscribe, ctx: &mut Self::Context) -> Self::Result {
+        let channels = if msg.channels.is_empty() {
+            self.shard_channels.keys().cloned().collect()
+        } else {
+            msg.channels
+        };
+
+        let mut nodes: HashMap<String, Vec<String>> = HashMap::new();
+        for channel in channels {
+            if let Some(subscription) = self.shard_channels.remove(&channel) {
+                nodes.entry(subscription.node).or_default().push(channel);
+            }
+        }
+
+        let requests: Vec<_> = nodes
+            .into_iter()
+            .map(|(node, channels)| {
+                self.subscriber(node, ctx).send(SUnsubscribe { channels })
+            })
+            .collect();
+
+        Box::pin(future::join_all(requests).map(all_ok))
+    }
+}
+
+impl Handler<SubscribeKeyspace> for RedisClusterActor {
+    type Result = ResponseFuture<Result<(), Error>>;
+
+    fn handle(
+        &mut self,
+        msg: SubscribeKeyspace,
+        ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let masters: HashSet<String> = self.slots.iter().map(Slots::master).collect();
+        if masters.is_empty() {
+            return Box::pin(future::err(Error::NotConnected));
+        }
+
+        let requests: Vec<_> = masters
+            .into_iter()
+            .map(|master| {
+                self.keyspace_nodes.insert(master.clone());
+                self.subscriber(master, ctx).send(msg.clone())
+            })
+            .collect();
+        self.keyspace.push(msg);
+
+        Box::pin(future::join_all(requests).map(all_ok))
+    }
+}
+
+/// Collapse the replies of the requests sent to several nodes
+fn all_ok(results: Vec<Result<Result<(), Error>, MailboxError>>) -> Result<(), Error> {
+    for res in results {
+        match res {
+            Ok(Ok(())) => (),
+            Ok(Err(e)) => return Err(e),
+            Err(_canceled) => return Err(Error::Disconnected),
+        }
+    }
+    Ok(())
+}
+
+impl Handler<ShardUnsubscribed> for RedisClusterActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: ShardUnsubscribed, ctx: &mut Self::Context) {
+        let ShardUnsubscribed { channel, listeners } = msg;
+        self.shard_channels.remove(&channel);
+
+        // the slot probably migrated, look up the new owner
+        let refreshed = self.next_refresh(ctx);
+        ctx.spawn(refreshed.into_actor(self).map(move |_, this, ctx| {
+            this.resubscribe(channel, listeners, ctx);
+        }));
+    }
+}
+
+impl Handler<Shutdown> for RedisClusterActor {
+    type Result = ResponseActFuture<Self, Result<(), Error>>;
+
+    fn handle(&mut self, msg: Shutdown, _ctx: &mut Self::Context) -> Self::Result {
+        info!("Shutting down cluster connections");
+        // no new connection is made to follow redirections from now on
+        self.shutdown = true;
+        self.subscribers.clear();
+        self.shard_channels.clear();
+
+        let shutdowns = self
+            .connections
+            .values()
+            .chain(self.replicas.values())
+            .map(|pool| pool.shutdown(msg.timeout))
+            .collect::<Vec<_>>();
+        Box::new(
+            future::join_all(shutdowns)
+                .into_actor(self)
+                .map(|_, _, ctx| {
+                    ctx.stop();
+                    Ok(())
+                }),
+        )
+    }
+}
+
 #[doc(hidden)]
 pub struct Stop;
 
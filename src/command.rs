@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use crate::slot::{HashError, Hasher};
 use crate::Error;
 use crate::RespError;
@@ -30,6 +32,193 @@ pub trait Command {
         self.hash_keys(&mut hasher)?;
         Ok(hasher.get())
     }
+
+    /// How long the server may block before replying, `Some` only for
+    /// blocking commands. They are sent over connections of their own so the
+    /// other commands are not stalled behind them.
+    /// A zero duration blocks until a reply is available.
+    fn blocking_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Whether the command only reads, so that a cluster replica can serve it
+    fn is_read_only(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug)]
+pub struct Auth {
+    /// ACL username, `None` authenticates the `default` user
+    pub username: Option<String>,
+    pub password: String,
+}
+
+impl Message for Auth {
+    type Result = Result<Result<(), String>, Error>;
+}
+
+impl Command for Auth {
+    type Output = Result<(), String>;
+
+    fn into_request(self) -> RespValue {
+        match self.username {
+            Some(username) => resp_array!["AUTH", username, self.password],
+            None => resp_array!["AUTH", self.password],
+        }
+    }
+
+    fn from_response(res: RespValue) -> Result<Self::Output, RespError> {
+        match res {
+            RespValue::SimpleString(ref s) if s == "OK" => Ok(Ok(())),
+            RespValue::Error(e) => Ok(Err(e)),
+            res => Err(RespError::RESP(
+                "invalid response for AUTH".into(),
+                Some(res),
+            )),
+        }
+    }
+
+    fn hash_keys(&self, _hasher: &mut Hasher) -> Result<(), HashError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Hello {
+    pub protover: usize,
+    /// ACL username and password to authenticate with
+    pub auth: Option<(String, String)>,
+}
+
+impl Message for Hello {
+    type Result = Result<Result<(), String>, Error>;
+}
+
+impl Command for Hello {
+    type Output = Result<(), String>;
+
+    fn into_request(self) -> RespValue {
+        match self.auth {
+            Some((username, password)) => resp_array![
+                "HELLO",
+                self.protover.to_string(),
+                "AUTH",
+                username,
+                password
+            ],
+            None => resp_array!["HELLO", self.protover.to_string()],
+        }
+    }
+
+    fn from_response(res: RespValue) -> Result<Self::Output, RespError> {
+        match res {
+            RespValue::Array(_) => Ok(Ok(())),
+            RespValue::Error(e) => Ok(Err(e)),
+            res => Err(RespError::RESP(
+                "invalid response for HELLO".into(),
+                Some(res),
+            )),
+        }
+    }
+
+    fn hash_keys(&self, _hasher: &mut Hasher) -> Result<(), HashError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Select {
+    pub db: usize,
+}
+
+impl Message for Select {
+    type Result = Result<(), Error>;
+}
+
+impl Command for Select {
+    type Output = ();
+
+    fn into_request(self) -> RespValue {
+        resp_array!["SELECT", self.db.to_string()]
+    }
+
+    fn from_response(res: RespValue) -> Result<Self::Output, RespError> {
+        match res {
+            RespValue::SimpleString(ref s) if s == "OK" => Ok(()),
+            res => Err(RespError::RESP(
+                "invalid response for SELECT".into(),
+                Some(res),
+            )),
+        }
+    }
+
+    fn hash_keys(&self, _hasher: &mut Hasher) -> Result<(), HashError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct ClientSetName {
+    pub name: String,
+}
+
+impl Message for ClientSetName {
+    type Result = Result<(), Error>;
+}
+
+impl Command for ClientSetName {
+    type Output = ();
+
+    fn into_request(self) -> RespValue {
+        resp_array!["CLIENT", "SETNAME", self.name]
+    }
+
+    fn from_response(res: RespValue) -> Result<Self::Output, RespError> {
+        match res {
+            RespValue::SimpleString(ref s) if s == "OK" => Ok(()),
+            res => Err(RespError::RESP(
+                "invalid response for CLIENT SETNAME".into(),
+                Some(res),
+            )),
+        }
+    }
+
+    fn hash_keys(&self, _hasher: &mut Hasher) -> Result<(), HashError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct ConfigSet {
+    pub parameter: String,
+    pub value: String,
+}
+
+impl Message for ConfigSet {
+    type Result = Result<(), Error>;
+}
+
+impl Command for ConfigSet {
+    type Output = ();
+
+    fn into_request(self) -> RespValue {
+        resp_array!["CONFIG", "SET", self.parameter, self.value]
+    }
+
+    fn from_response(res: RespValue) -> Result<Self::Output, RespError> {
+        match res {
+            RespValue::SimpleString(ref s) if s == "OK" => Ok(()),
+            res => Err(RespError::RESP(
+                "invalid response for CONFIG SET".into(),
+                Some(res),
+            )),
+        }
+    }
+
+    fn hash_keys(&self, _hasher: &mut Hasher) -> Result<(), HashError> {
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -62,6 +251,10 @@ impl Command for Get {
     fn hash_keys(&self, hasher: &mut Hasher) -> Result<(), HashError> {
         hasher.hash_str(&self.key)
     }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
 }
 
 #[derive(Debug)]
@@ -198,6 +391,13 @@ impl Slots {
     pub fn master(&self) -> String {
         format!("{}:{}", self.nodes[0].0, self.nodes[0].1)
     }
+
+    /// Addresses of the replicas of the master
+    pub fn replicas(&self) -> impl Iterator<Item = String> + '_ {
+        self.nodes[1..]
+            .iter()
+            .map(|(host, port, _)| format!("{}:{}", host, port))
+    }
 }
 
 impl Message for ClusterSlots {
@@ -282,6 +482,10 @@ impl Command for ClusterSlots {
     fn hash_keys(&self, _hasher: &mut Hasher) -> Result<(), HashError> {
         Ok(())
     }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
 }
 
 #[derive(Debug)]
@@ -313,6 +517,198 @@ impl Command for Asking {
     }
 }
 
+/// Allow reads from a cluster replica over this connection
+#[derive(Debug)]
+pub struct ReadOnly;
+
+impl Message for ReadOnly {
+    type Result = Result<(), Error>;
+}
+
+impl Command for ReadOnly {
+    type Output = ();
+
+    fn into_request(self) -> RespValue {
+        resp_array!["READONLY"]
+    }
+
+    fn from_response(res: RespValue) -> Result<Self::Output, RespError> {
+        match res {
+            RespValue::SimpleString(ref s) if s == "OK" => Ok(()),
+            res => Err(RespError::RESP(
+                "invalid response for READONLY".into(),
+                Some(res),
+            )),
+        }
+    }
+
+    fn hash_keys(&self, _hasher: &mut Hasher) -> Result<(), HashError> {
+        Ok(())
+    }
+}
+
+/// Start a transaction. Sent on its own, commands of other actors can be
+/// interleaved with the queued ones, so use `transaction::Transaction` instead.
+#[derive(Debug)]
+pub struct Multi;
+
+impl Message for Multi {
+    type Result = Result<(), Error>;
+}
+
+impl Command for Multi {
+    type Output = ();
+
+    fn into_request(self) -> RespValue {
+        resp_array!["MULTI"]
+    }
+
+    fn from_response(res: RespValue) -> Result<Self::Output, RespError> {
+        match res {
+            RespValue::SimpleString(ref s) if s == "OK" => Ok(()),
+            res => Err(RespError::RESP(
+                "invalid response for MULTI".into(),
+                Some(res),
+            )),
+        }
+    }
+
+    fn hash_keys(&self, _hasher: &mut Hasher) -> Result<(), HashError> {
+        Ok(())
+    }
+}
+
+/// Discard the queued commands, see `Multi`
+#[derive(Debug)]
+pub struct Discard;
+
+impl Message for Discard {
+    type Result = Result<(), Error>;
+}
+
+impl Command for Discard {
+    type Output = ();
+
+    fn into_request(self) -> RespValue {
+        resp_array!["DISCARD"]
+    }
+
+    fn from_response(res: RespValue) -> Result<Self::Output, RespError> {
+        match res {
+            RespValue::SimpleString(ref s) if s == "OK" => Ok(()),
+            res => Err(RespError::RESP(
+                "invalid response for DISCARD".into(),
+                Some(res),
+            )),
+        }
+    }
+
+    fn hash_keys(&self, _hasher: &mut Hasher) -> Result<(), HashError> {
+        Ok(())
+    }
+}
+
+/// Execute the queued commands, see `Multi`
+#[derive(Debug)]
+pub struct Exec;
+
+impl Message for Exec {
+    type Result = Result<Option<Vec<RespValue>>, Error>;
+}
+
+impl Command for Exec {
+    /// Replies of the queued commands, `None` if the transaction was aborted
+    type Output = Option<Vec<RespValue>>;
+
+    fn into_request(self) -> RespValue {
+        resp_array!["EXEC"]
+    }
+
+    fn from_response(res: RespValue) -> Result<Self::Output, RespError> {
+        match res {
+            RespValue::Array(replies) => Ok(Some(replies)),
+            RespValue::Nil => Ok(None),
+            res => Err(RespError::RESP(
+                "invalid response for EXEC".into(),
+                Some(res),
+            )),
+        }
+    }
+
+    fn hash_keys(&self, _hasher: &mut Hasher) -> Result<(), HashError> {
+        Ok(())
+    }
+}
+
+/// Abort the next transaction if any of `keys` is modified before `EXEC`.
+/// The keys are watched on the connection, so use `transaction::CheckAndSet`
+/// to have a connection of its own.
+#[derive(Debug)]
+pub struct Watch {
+    pub keys: Vec<String>,
+}
+
+impl Message for Watch {
+    type Result = Result<(), Error>;
+}
+
+impl Command for Watch {
+    type Output = ();
+
+    fn into_request(self) -> RespValue {
+        let mut v = vec![RespValue::BulkString(b"WATCH".to_vec())];
+        v.extend(self.keys.into_iter().map(Into::into));
+        RespValue::Array(v)
+    }
+
+    fn from_response(res: RespValue) -> Result<Self::Output, RespError> {
+        match res {
+            RespValue::SimpleString(ref s) if s == "OK" => Ok(()),
+            res => Err(RespError::RESP(
+                "invalid response for WATCH".into(),
+                Some(res),
+            )),
+        }
+    }
+
+    fn hash_keys(&self, hasher: &mut Hasher) -> Result<(), HashError> {
+        for key in self.keys.iter() {
+            hasher.hash_str(key)?
+        }
+        Ok(())
+    }
+}
+
+/// Forget the keys watched with `Watch`
+#[derive(Debug)]
+pub struct Unwatch;
+
+impl Message for Unwatch {
+    type Result = Result<(), Error>;
+}
+
+impl Command for Unwatch {
+    type Output = ();
+
+    fn into_request(self) -> RespValue {
+        resp_array!["UNWATCH"]
+    }
+
+    fn from_response(res: RespValue) -> Result<Self::Output, RespError> {
+        match res {
+            RespValue::SimpleString(ref s) if s == "OK" => Ok(()),
+            res => Err(RespError::RESP(
+                "invalid response for UNWATCH".into(),
+                Some(res),
+            )),
+        }
+    }
+
+    fn hash_keys(&self, _hasher: &mut Hasher) -> Result<(), HashError> {
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub enum TtlError {
     KeyNotExist,
@@ -365,6 +761,10 @@ impl Command for Ttl {
     fn hash_keys(&self, hasher: &mut Hasher) -> Result<(), HashError> {
         hasher.hash_str(&self.key)
     }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
 }
 
 #[derive(Debug)]
@@ -399,6 +799,10 @@ impl Command for Pttl {
     fn hash_keys(&self, hasher: &mut Hasher) -> Result<(), HashError> {
         hasher.hash_str(&self.key)
     }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
 }
 
 #[derive(Debug)]
@@ -531,6 +935,350 @@ impl Command for DecrBy {
     }
 }
 
+/// Remove and get the first element of the first non-empty list of `keys`,
+/// blocking up to `timeout` until an element is available
+#[derive(Debug)]
+pub struct BLPop {
+    pub keys: Vec<String>,
+    /// Zero blocks until an element is available. Fractions of a second
+    /// require Redis 6.0.
+    pub timeout: Duration,
+}
+
+impl Message for BLPop {
+    type Result = Result<Option<(String, Vec<u8>)>, Error>;
+}
+
+impl Command for BLPop {
+    /// Key of the list and the element, `None` if `timeout` elapsed
+    type Output = Option<(String, Vec<u8>)>;
+
+    fn into_request(self) -> RespValue {
+        blocking_pop_request("BLPOP", self.keys, self.timeout)
+    }
+
+    fn from_response(res: RespValue) -> Result<Self::Output, RespError> {
+        blocking_pop_response("BLPOP", res)
+    }
+
+    fn hash_keys(&self, hasher: &mut Hasher) -> Result<(), HashError> {
+        for key in self.keys.iter() {
+            hasher.hash_str(key)?
+        }
+        Ok(())
+    }
+
+    fn blocking_timeout(&self) -> Option<Duration> {
+        Some(self.timeout)
+    }
+}
+
+/// Remove and get the last element of the first non-empty list of `keys`,
+/// blocking up to `timeout` until an element is available
+#[derive(Debug)]
+pub struct BRPop {
+    pub keys: Vec<String>,
+    /// Zero blocks until an element is available. Fractions of a second
+    /// require Redis 6.0.
+    pub timeout: Duration,
+}
+
+impl Message for BRPop {
+    type Result = Result<Option<(String, Vec<u8>)>, Error>;
+}
+
+impl Command for BRPop {
+    /// Key of the list and the element, `None` if `timeout` elapsed
+    type Output = Option<(String, Vec<u8>)>;
+
+    fn into_request(self) -> RespValue {
+        blocking_pop_request("BRPOP", self.keys, self.timeout)
+    }
+
+    fn from_response(res: RespValue) -> Result<Self::Output, RespError> {
+        blocking_pop_response("BRPOP", res)
+    }
+
+    fn hash_keys(&self, hasher: &mut Hasher) -> Result<(), HashError> {
+        for key in self.keys.iter() {
+            hasher.hash_str(key)?
+        }
+        Ok(())
+    }
+
+    fn blocking_timeout(&self) -> Option<Duration> {
+        Some(self.timeout)
+    }
+}
+
+fn blocking_pop_request(name: &str, keys: Vec<String>, timeout: Duration) -> RespValue {
+    let mut v = vec![RespValue::BulkString(name.as_bytes().to_vec())];
+    v.extend(keys.into_iter().map(Into::into));
+    v.push(blocking_timeout(timeout).into());
+    RespValue::Array(v)
+}
+
+/// Seconds of a blocking timeout. Servers older than 6.0 only accept whole
+/// seconds, so fractions are sent only when there are any.
+fn blocking_timeout(timeout: Duration) -> String {
+    if timeout.subsec_nanos() == 0 {
+        timeout.as_secs().to_string()
+    } else {
+        timeout.as_secs_f64().to_string()
+    }
+}
+
+fn blocking_pop_response(
+    name: &str,
+    res: RespValue,
+) -> Result<Option<(String, Vec<u8>)>, RespError> {
+    use redis_async::resp::FromResp;
+
+    match res {
+        RespValue::Nil => Ok(None),
+        RespValue::Array(mut v) if v.len() == 2 => {
+            let value = Vec::<u8>::from_resp(v.pop().unwrap())?;
+            let key = String::from_resp(v.pop().unwrap())?;
+            Ok(Some((key, value)))
+        }
+        RespValue::Error(e) => Err(RespError::Remote(e)),
+        res => Err(RespError::RESP(
+            format!("invalid response for {}", name),
+            Some(res),
+        )),
+    }
+}
+
+/// Remove and get the member with the lowest score of the first non-empty
+/// sorted set of `keys`, blocking up to `timeout` until a member is available
+#[derive(Debug)]
+pub struct BZPopMin {
+    pub keys: Vec<String>,
+    /// Zero blocks until a member is available. Fractions of a second
+    /// require Redis 6.0.
+    pub timeout: Duration,
+}
+
+impl Message for BZPopMin {
+    type Result = Result<Option<(String, Vec<u8>, f64)>, Error>;
+}
+
+impl Command for BZPopMin {
+    /// Key of the sorted set, the member and its score, `None` if `timeout` elapsed
+    type Output = Option<(String, Vec<u8>, f64)>;
+
+    fn into_request(self) -> RespValue {
+        blocking_pop_request("BZPOPMIN", self.keys, self.timeout)
+    }
+
+    fn from_response(res: RespValue) -> Result<Self::Output, RespError> {
+        use redis_async::resp::FromResp;
+
+        match res {
+            RespValue::Nil => Ok(None),
+            RespValue::Array(mut v) if v.len() == 3 => {
+                let score = String::from_resp(v.pop().unwrap())?;
+                let score = score.parse().map_err(|_| {
+                    RespError::RESP(format!("invalid score {}", score), None)
+                })?;
+                let member = Vec::<u8>::from_resp(v.pop().unwrap())?;
+                let key = String::from_resp(v.pop().unwrap())?;
+                Ok(Some((key, member, score)))
+            }
+            RespValue::Error(e) => Err(RespError::Remote(e)),
+            res => Err(RespError::RESP(
+                "invalid response for BZPOPMIN".into(),
+                Some(res),
+            )),
+        }
+    }
+
+    fn hash_keys(&self, hasher: &mut Hasher) -> Result<(), HashError> {
+        for key in self.keys.iter() {
+            hasher.hash_str(key)?
+        }
+        Ok(())
+    }
+
+    fn blocking_timeout(&self) -> Option<Duration> {
+        Some(self.timeout)
+    }
+}
+
+/// Entry of a stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamEntry {
+    pub id: String,
+    pub fields: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+/// Read entries of streams, blocking with `block` until one is available
+#[derive(Debug)]
+pub struct XRead {
+    pub count: Option<usize>,
+    /// Block up to this long when there is no entry, zero blocks until an
+    /// entry is available. `None` does not block.
+    pub block: Option<Duration>,
+    /// Name of each stream with the ID to read the entries after, e.g. `$`
+    /// for the entries added from now on
+    pub streams: Vec<(String, String)>,
+}
+
+impl Message for XRead {
+    type Result = Result<Vec<(String, Vec<StreamEntry>)>, Error>;
+}
+
+impl Command for XRead {
+    /// Entries of each stream having any, empty if `block` elapsed
+    type Output = Vec<(String, Vec<StreamEntry>)>;
+
+    fn into_request(self) -> RespValue {
+        let mut v = vec![RespValue::BulkString(b"XREAD".to_vec())];
+        if let Some(count) = self.count {
+            v.push("COUNT".into());
+            v.push(count.to_string().into());
+        }
+        if let Some(block) = self.block {
+            v.push("BLOCK".into());
+            v.push(block.as_millis().to_string().into());
+        }
+        v.push("STREAMS".into());
+        let (names, ids): (Vec<_>, Vec<_>) = self.streams.into_iter().unzip();
+        v.extend(names.into_iter().map(Into::into));
+        v.extend(ids.into_iter().map(Into::into));
+        RespValue::Array(v)
+    }
+
+    fn from_response(res: RespValue) -> Result<Self::Output, RespError> {
+        use redis_async::resp::FromResp;
+
+        fn invalid(res: RespValue) -> RespError {
+            RespError::RESP("invalid response for XREAD".into(), Some(res))
+        }
+
+        fn entry(res: RespValue) -> Result<StreamEntry, RespError> {
+            match res {
+                RespValue::Array(mut v) if v.len() == 2 => {
+                    let fields = match v.pop().unwrap() {
+                        RespValue::Array(fields) => fields,
+                        res => return Err(invalid(res)),
+                    };
+                    let mut fields = fields.into_iter();
+                    let mut pairs = vec![];
+                    while let (Some(field), Some(value)) = (fields.next(), fields.next())
+                    {
+                        pairs.push((
+                            Vec::<u8>::from_resp(field)?,
+                            Vec::<u8>::from_resp(value)?,
+                        ));
+                    }
+                    Ok(StreamEntry {
+                        id: String::from_resp(v.pop().unwrap())?,
+                        fields: pairs,
+                    })
+                }
+                res => Err(invalid(res)),
+            }
+        }
+
+        let streams = match res {
+            RespValue::Nil => return Ok(vec![]),
+            RespValue::Array(streams) => streams,
+            RespValue::Error(e) => return Err(RespError::Remote(e)),
+            res => return Err(invalid(res)),
+        };
+
+        let mut output = vec![];
+        let mut streams = streams.into_iter();
+        while let Some(stream) = streams.next() {
+            let (name, entries) = match stream {
+                RespValue::Array(mut pair) if pair.len() == 2 => {
+                    let entries = pair.pop().unwrap();
+                    (pair.pop().unwrap(), entries)
+                }
+                // RESP3 map, flattened by `Resp3Value::into_resp2`
+                name => match streams.next() {
+                    Some(entries) => (name, entries),
+                    None => return Err(invalid(name)),
+                },
+            };
+            let entries = match entries {
+                RespValue::Array(entries) => {
+                    entries.into_iter().map(entry).collect::<Result<_, _>>()?
+                }
+                res => return Err(invalid(res)),
+            };
+            output.push((String::from_resp(name)?, entries));
+        }
+        Ok(output)
+    }
+
+    fn hash_keys(&self, hasher: &mut Hasher) -> Result<(), HashError> {
+        for (name, _) in self.streams.iter() {
+            hasher.hash_str(name)?
+        }
+        Ok(())
+    }
+
+    fn blocking_timeout(&self) -> Option<Duration> {
+        self.block
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+}
+
+/// Ask a sentinel for the address of the master it monitors as `master_name`
+#[derive(Debug)]
+pub struct SentinelGetMasterAddrByName {
+    pub master_name: String,
+}
+
+impl Message for SentinelGetMasterAddrByName {
+    type Result = Result<Option<(String, u16)>, Error>;
+}
+
+impl Command for SentinelGetMasterAddrByName {
+    /// `None` if the master is unknown to the sentinel
+    type Output = Option<(String, u16)>;
+
+    fn into_request(self) -> RespValue {
+        resp_array!["SENTINEL", "GET-MASTER-ADDR-BY-NAME", self.master_name]
+    }
+
+    fn from_response(res: RespValue) -> Result<Self::Output, RespError> {
+        use redis_async::resp::FromResp;
+
+        match res {
+            RespValue::Nil => Ok(None),
+            RespValue::Array(mut addr) if addr.len() == 2 => {
+                let port = String::from_resp(addr.pop().unwrap())?;
+                let host = String::from_resp(addr.pop().unwrap())?;
+                match port.parse() {
+                    Ok(port) => Ok(Some((host, port))),
+                    Err(_) => Err(RespError::RESP(
+                        "invalid port for SENTINEL GET-MASTER-ADDR-BY-NAME".into(),
+                        Some(RespValue::BulkString(port.into_bytes())),
+                    )),
+                }
+            }
+            res => Err(RespError::RESP(
+                "invalid response for SENTINEL GET-MASTER-ADDR-BY-NAME".into(),
+                Some(res),
+            )),
+        }
+    }
+
+    fn hash_keys(&self, _hasher: &mut Hasher) -> Result<(), HashError> {
+        Ok(())
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+}
+
 #[derive(Debug)]
 pub struct Ping(pub Option<String>);
 
@@ -561,6 +1309,77 @@ impl Command for Ping {
     fn hash_keys(&self, _hasher: &mut Hasher) -> Result<(), HashError> {
         Ok(())
     }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug)]
+pub struct Publish {
+    pub channel: String,
+    pub message: String,
+}
+
+impl Message for Publish {
+    type Result = Result<i64, Error>;
+}
+
+impl Command for Publish {
+    /// Number of the clients which received the message
+    type Output = i64;
+
+    fn into_request(self) -> RespValue {
+        resp_array!["PUBLISH", self.channel, self.message]
+    }
+
+    fn from_response(res: RespValue) -> Result<Self::Output, RespError> {
+        match res {
+            RespValue::Integer(x) => Ok(x),
+            res => Err(RespError::RESP(
+                "invalid response for PUBLISH".into(),
+                Some(res),
+            )),
+        }
+    }
+
+    fn hash_keys(&self, _hasher: &mut Hasher) -> Result<(), HashError> {
+        Ok(())
+    }
+}
+
+/// Publish to a shard channel, routed to the owner of its slot in cluster mode
+#[derive(Debug)]
+pub struct SPublish {
+    pub channel: String,
+    pub message: String,
+}
+
+impl Message for SPublish {
+    type Result = Result<i64, Error>;
+}
+
+impl Command for SPublish {
+    /// Number of the clients which received the message
+    type Output = i64;
+
+    fn into_request(self) -> RespValue {
+        resp_array!["SPUBLISH", self.channel, self.message]
+    }
+
+    fn from_response(res: RespValue) -> Result<Self::Output, RespError> {
+        match res {
+            RespValue::Integer(x) => Ok(x),
+            res => Err(RespError::RESP(
+                "invalid response for SPUBLISH".into(),
+                Some(res),
+            )),
+        }
+    }
+
+    fn hash_keys(&self, hasher: &mut Hasher) -> Result<(), HashError> {
+        hasher.hash_str(&self.channel)
+    }
 }
 
 #[derive(Debug)]
@@ -590,6 +1409,10 @@ impl Command for Echo {
     fn hash_keys(&self, _hasher: &mut Hasher) -> Result<(), HashError> {
         Ok(())
     }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
 }
 
 #[derive(Debug)]
@@ -968,6 +1791,10 @@ impl Command for ClusterCountKeysInSlot {
     fn hash_keys(&self, hasher: &mut Hasher) -> Result<(), HashError> {
         hasher.set(self.target_node_slot)
     }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
 }
 
 #[derive(Debug)]
@@ -1008,6 +1835,10 @@ impl Command for ClusterGetKeysInSlot {
     fn hash_keys(&self, hasher: &mut Hasher) -> Result<(), HashError> {
         hasher.set(self.target_node_slot)
     }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
 }
 
 #[derive(Debug)]
@@ -1053,3 +1884,18 @@ impl Command for Migrate {
         hasher.set(self.target_node_slot)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::blocking_timeout;
+
+    #[test]
+    fn test_blocking_timeout() {
+        assert_eq!(blocking_timeout(Duration::from_secs(0)), "0");
+        assert_eq!(blocking_timeout(Duration::from_secs(5)), "5");
+        assert_eq!(blocking_timeout(Duration::from_millis(500)), "0.5");
+        assert_eq!(blocking_timeout(Duration::from_millis(1500)), "1.5");
+    }
+}
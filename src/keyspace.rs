@@ -0,0 +1,138 @@
+//! Keyspace notifications
+//!
+//! The server publishes the changes of keys to `__keyspace@<db>__:<key>` and
+//! `__keyevent@<db>__:<event>` once enabled with `notify-keyspace-events`.
+use crate::listener::Listener;
+use crate::pubsub::PubSubMessage;
+use actix::prelude::*;
+
+/// Change of a key
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyspaceEvent {
+    pub db: usize,
+    pub key: String,
+    /// Name of the event, e.g. `set`, `del` or `expired`
+    pub event: String,
+}
+
+impl Message for KeyspaceEvent {
+    type Result = ();
+}
+
+impl KeyspaceEvent {
+    /// Parse a message published to a keyspace or keyevent channel
+    pub fn from_message(msg: &PubSubMessage) -> Option<Self> {
+        let mut parts = msg.channel.splitn(2, '@');
+        let keyspace = match parts.next() {
+            Some("__keyspace") => true,
+            Some("__keyevent") => false,
+            _ => return None,
+        };
+        let rest = parts.next()?;
+
+        let end = rest.find("__:")?;
+        let db = rest[..end].parse().ok()?;
+        let name = rest[end + "__:".len()..].to_string();
+        let payload = String::from_utf8_lossy(&msg.payload).into_owned();
+
+        Some(if keyspace {
+            KeyspaceEvent {
+                db,
+                key: name,
+                event: payload,
+            }
+        } else {
+            KeyspaceEvent {
+                db,
+                key: payload,
+                event: name,
+            }
+        })
+    }
+}
+
+/// Receiver of keyspace events
+pub type KeyspaceListener = Listener<KeyspaceEvent>;
+
+/// Family of the channels of keyspace notifications
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyspaceChannel {
+    /// `__keyspace@<db>__:<key>`, `pattern` matches keys
+    Keyspace,
+    /// `__keyevent@<db>__:<event>`, `pattern` matches events
+    Keyevent,
+}
+
+/// Subscribe to keyspace notifications.
+///
+/// `RedisClusterActor` subscribes on every master node of the cluster.
+#[derive(Debug, Clone)]
+pub struct SubscribeKeyspace {
+    /// Value of `notify-keyspace-events` to set with `CONFIG SET` before
+    /// subscribing and after every reconnection, e.g. `KEA`. The server
+    /// configuration is kept if `None`.
+    pub config: Option<String>,
+    pub channel: KeyspaceChannel,
+    /// Database to watch, every database if `None`
+    pub db: Option<usize>,
+    pub pattern: String,
+    pub listener: KeyspaceListener,
+}
+
+impl Message for SubscribeKeyspace {
+    type Result = Result<(), crate::Error>;
+}
+
+impl SubscribeKeyspace {
+    /// Pattern of the channels, which can be passed to `PUnsubscribe`
+    pub fn channel_pattern(&self) -> String {
+        let family = match self.channel {
+            KeyspaceChannel::Keyspace => "keyspace",
+            KeyspaceChannel::Keyevent => "keyevent",
+        };
+        let db = match self.db {
+            Some(db) => db.to_string(),
+            None => "*".to_string(),
+        };
+        format!("__{}@{}__:{}", family, db, self.pattern)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::KeyspaceEvent;
+    use crate::pubsub::PubSubMessage;
+
+    fn message(channel: &str, payload: &str) -> PubSubMessage {
+        PubSubMessage {
+            channel: channel.into(),
+            pattern: None,
+            payload: payload.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_from_message() {
+        assert_eq!(
+            KeyspaceEvent::from_message(&message("__keyspace@0__:session:1", "expired")),
+            Some(KeyspaceEvent {
+                db: 0,
+                key: "session:1".into(),
+                event: "expired".into(),
+            })
+        );
+        assert_eq!(
+            KeyspaceEvent::from_message(&message("__keyevent@12__:del", "foo__:bar")),
+            Some(KeyspaceEvent {
+                db: 12,
+                key: "foo__:bar".into(),
+                event: "del".into(),
+            })
+        );
+        assert_eq!(KeyspaceEvent::from_message(&message("news", "hello")), None);
+        assert_eq!(
+            KeyspaceEvent::from_message(&message("__keyspace@x__:foo", "set")),
+            None
+        );
+    }
+}
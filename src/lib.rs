@@ -14,12 +14,29 @@ extern crate redis_async;
 #[macro_use]
 extern crate derive_more;
 
+pub mod blocking;
 pub mod cluster;
 pub mod command;
+pub mod keyspace;
+pub mod listener;
+pub mod pipeline;
+pub mod pool;
+pub mod pubsub;
 pub mod redis;
+pub mod resp3;
+pub mod sentinel;
 pub mod slot;
-pub use crate::cluster::RedisClusterActor;
-pub use crate::redis::RedisActor;
+pub mod transaction;
+mod transport;
+pub use crate::cluster::{RedisClusterActor, RedisClusterActorBuilder};
+pub use crate::listener::Listener;
+pub use crate::pool::Pool;
+pub use crate::pubsub::RedisSubscriber;
+pub use crate::redis::{ConnectOptions, RedisActor, RedisActorBuilder};
+pub use crate::resp3::Resp3Value;
+pub use crate::sentinel::Sentinel;
+#[cfg(feature = "tls")]
+pub use crate::transport::TlsConfig;
 
 #[cfg(feature = "web")]
 mod session;
@@ -45,6 +62,26 @@ pub enum Error {
     /// I/O Error
     #[display(fmt = "Redis: I/O error {}", _0)]
     IoError(std::io::Error),
+    /// Failed to resolve or connect to the server
+    #[display(fmt = "Redis: Can not connect {}", _0)]
+    Connect(actix::actors::resolver::ResolverError),
+    /// No reply arrived within the response timeout
+    #[display(fmt = "Redis: Timeout")]
+    Timeout,
+    /// Too many requests are waiting for their replies, see `max_in_flight`
+    #[display(fmt = "Redis: Overloaded")]
+    Overloaded,
+    /// The actor was asked to shut down with `Shutdown`
+    #[display(fmt = "Redis: Shutting down")]
+    ShuttingDown,
+    /// No sentinel could tell the address of the master
+    #[display(fmt = "Redis: Sentinel {}", _0)]
+    #[from(ignore)]
+    Sentinel(String),
+    /// Server rejected the credentials sent with `AUTH`
+    #[display(fmt = "Redis: Authentication failed {}", _0)]
+    #[from(ignore)]
+    Auth(String),
 }
 
 #[cfg(feature = "web")]
@@ -0,0 +1,78 @@
+//! Receivers of the messages and events produced by the actors
+use std::fmt;
+
+use actix::prelude::*;
+use futures::channel::mpsc;
+
+/// Receiver of messages of type `M`, either an actor or a `Stream`
+pub enum Listener<M>
+where
+    M: Message + Send,
+    M::Result: Send,
+{
+    Recipient(Recipient<M>),
+    Stream(mpsc::UnboundedSender<M>),
+}
+
+impl<M> Listener<M>
+where
+    M: Message + Send,
+    M::Result: Send,
+{
+    /// Create a listener delivering the messages to the returned `Stream`
+    pub fn stream() -> (Self, mpsc::UnboundedReceiver<M>) {
+        let (tx, rx) = mpsc::unbounded();
+        (Listener::Stream(tx), rx)
+    }
+
+    /// Deliver `msg`. Returns false if the receiver is gone.
+    pub(crate) fn deliver(&self, msg: M) -> bool {
+        match self {
+            Listener::Recipient(recipient) => match recipient.do_send(msg) {
+                Ok(()) => true,
+                Err(SendError::Full(_)) => {
+                    warn!("Mailbox of listener is full, message dropped");
+                    true
+                }
+                Err(SendError::Closed(_)) => false,
+            },
+            Listener::Stream(tx) => tx.unbounded_send(msg).is_ok(),
+        }
+    }
+}
+
+impl<M> Clone for Listener<M>
+where
+    M: Message + Send,
+    M::Result: Send,
+{
+    fn clone(&self) -> Self {
+        match self {
+            Listener::Recipient(recipient) => Listener::Recipient(recipient.clone()),
+            Listener::Stream(tx) => Listener::Stream(tx.clone()),
+        }
+    }
+}
+
+impl<M> fmt::Debug for Listener<M>
+where
+    M: Message + Send,
+    M::Result: Send,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Listener::Recipient(_) => f.write_str("Listener::Recipient"),
+            Listener::Stream(_) => f.write_str("Listener::Stream"),
+        }
+    }
+}
+
+impl<M> From<Recipient<M>> for Listener<M>
+where
+    M: Message + Send,
+    M::Result: Send,
+{
+    fn from(recipient: Recipient<M>) -> Self {
+        Listener::Recipient(recipient)
+    }
+}
@@ -0,0 +1,80 @@
+//! Batches of commands written at once
+use actix::Message;
+use redis_async::resp::RespValue;
+
+use crate::command::Command;
+use crate::slot::HashError;
+use crate::Error;
+
+pub use crate::transaction::{Handle, Replies};
+
+/// Commands written to the connection in a single flush.
+///
+/// Unlike `Transaction`, the commands are not executed atomically and a
+/// failing command does not affect the others. `RedisClusterActor` sends the
+/// commands to the nodes serving their keys, so the keys of different commands
+/// may be in different slots.
+#[derive(Debug, Clone, Default)]
+pub struct Pipeline {
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Entry {
+    pub(crate) request: RespValue,
+    /// Slot of the keys of the request, `None` if it has no keys
+    pub(crate) slot: Result<Option<u16>, HashError>,
+}
+
+impl Message for Pipeline {
+    /// Replies of the commands, in the order they were added
+    type Result = Result<Replies, Error>;
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue `cmd`, returning the handle to get its reply from `Replies`
+    pub fn add<C: Command>(&mut self, cmd: C) -> Handle<C> {
+        let slot = cmd.key_slot();
+        self.entries.push(Entry {
+            request: cmd.into_request(),
+            slot,
+        });
+        Handle::new(self.entries.len() - 1)
+    }
+
+    /// Queue a raw request, returning its index in `Replies::into_inner`.
+    ///
+    /// The keys of a raw request are unknown, so `RedisClusterActor` sends it
+    /// to the node it got the slots from and relies on redirections.
+    pub fn add_raw(&mut self, req: RespValue) -> usize {
+        self.entries.push(Entry {
+            request: req,
+            slot: Ok(None),
+        });
+        self.entries.len() - 1
+    }
+
+    /// Number of the queued commands
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub(crate) fn into_requests(self) -> Vec<RespValue> {
+        self.entries
+            .into_iter()
+            .map(|entry| entry.request)
+            .collect()
+    }
+
+    pub(crate) fn into_entries(self) -> Vec<Entry> {
+        self.entries
+    }
+}
@@ -0,0 +1,180 @@
+//! Multiple connections to a single server
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix::dev::ToEnvelope;
+use actix::prelude::*;
+
+use crate::redis::{RedisActor, RedisActorBuilder, Shutdown};
+
+/// How `Pool` picks the connection of a request
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Selection {
+    /// Use the connections in turn
+    RoundRobin,
+    /// Use the connection with the fewest requests waiting for their replies
+    LeastPending,
+}
+
+/// `RedisActor` connections to a single server.
+///
+/// Requests are spread over the connections, so a large reply on one of them
+/// does not hold up the requests sent over the others. Clones share the
+/// connections.
+#[derive(Clone)]
+pub struct Pool {
+    connections: Vec<Connection>,
+    selection: Selection,
+    next: Arc<AtomicUsize>,
+}
+
+#[derive(Clone)]
+struct Connection {
+    addr: Addr<RedisActor>,
+    /// Number of the requests waiting for their replies
+    pending: Arc<AtomicUsize>,
+}
+
+/// Decrements the pending count of a connection once the reply arrives or
+/// the request is dropped
+struct PendingGuard(Arc<AtomicUsize>);
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Pool {
+    /// Start a pool of `size` connections to `addr` with the default settings.
+    pub fn start<S: Into<String>>(addr: S, size: usize) -> Pool {
+        Self::builder().size(size).start(addr)
+    }
+
+    /// Create a builder to configure `Pool` before starting it.
+    pub fn builder() -> PoolBuilder {
+        PoolBuilder::default()
+    }
+
+    /// Send `msg` over one of the connections, picked by `Selection`
+    pub fn send<M>(
+        &self,
+        msg: M,
+    ) -> impl Future<Output = Result<M::Result, MailboxError>>
+    where
+        M: Message + Send + 'static,
+        M::Result: Send,
+        RedisActor: Handler<M>,
+        <RedisActor as Actor>::Context: ToEnvelope<RedisActor, M>,
+    {
+        let connection = self.select();
+        connection.pending.fetch_add(1, Ordering::SeqCst);
+        let guard = PendingGuard(connection.pending.clone());
+        let res = connection.addr.send(msg);
+
+        async move {
+            let res = res.await;
+            drop(guard);
+            res
+        }
+    }
+
+    /// Send `msg` over one of the connections without waiting for the reply
+    pub fn do_send<M>(&self, msg: M)
+    where
+        M: Message + Send + 'static,
+        M::Result: Send,
+        RedisActor: Handler<M>,
+        <RedisActor as Actor>::Context: ToEnvelope<RedisActor, M>,
+    {
+        self.select().addr.do_send(msg)
+    }
+
+    /// Addresses of all connections, e.g. to send a message to each of them
+    pub fn connections(&self) -> impl Iterator<Item = &Addr<RedisActor>> {
+        self.connections.iter().map(|connection| &connection.addr)
+    }
+
+    /// Shut all connections down, see `Shutdown`
+    pub fn shutdown(&self, timeout: Duration) -> impl Future<Output = ()> {
+        let shutdowns = self
+            .connections()
+            .map(|addr| addr.send(Shutdown { timeout }))
+            .collect::<Vec<_>>();
+        async move {
+            futures::future::join_all(shutdowns).await;
+        }
+    }
+
+    fn select(&self) -> &Connection {
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % self.connections.len();
+        match self.selection {
+            Selection::RoundRobin => &self.connections[start],
+            // begin with the next one in turn to spread ties
+            Selection::LeastPending => self.connections[start..]
+                .iter()
+                .chain(self.connections[..start].iter())
+                .min_by_key(|connection| connection.pending.load(Ordering::SeqCst))
+                .unwrap(),
+        }
+    }
+}
+
+/// Builder of `Pool`
+#[derive(Debug, Clone)]
+pub struct PoolBuilder {
+    connection: RedisActorBuilder,
+    size: usize,
+    selection: Selection,
+}
+
+impl Default for PoolBuilder {
+    fn default() -> Self {
+        PoolBuilder {
+            connection: RedisActorBuilder::default(),
+            size: 1,
+            selection: Selection::RoundRobin,
+        }
+    }
+}
+
+impl PoolBuilder {
+    /// Configure the connections of the pool
+    pub fn connection(mut self, builder: RedisActorBuilder) -> Self {
+        self.connection = builder;
+        self
+    }
+
+    /// Set the number of connections. Default is 1.
+    pub fn size(mut self, size: usize) -> Self {
+        self.size = size.max(1);
+        self
+    }
+
+    /// Set how the connection of a request is picked. Default is round-robin.
+    pub fn selection(mut self, selection: Selection) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    pub(crate) fn connection_builder(&self) -> &RedisActorBuilder {
+        &self.connection
+    }
+
+    /// Start the connections to `addr`.
+    pub fn start<S: Into<String>>(&self, addr: S) -> Pool {
+        let addr = addr.into();
+        Pool {
+            connections: (0..self.size)
+                .map(|_| Connection {
+                    addr: self.connection.start(addr.clone()),
+                    pending: Arc::new(AtomicUsize::new(0)),
+                })
+                .collect(),
+            selection: self.selection,
+            next: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
@@ -0,0 +1,673 @@
+//! Pub/Sub subscriptions
+//!
+//! A connection in subscribed state only receives messages and confirmations,
+//! so subscriptions are served by `RedisSubscriber` instead of `RedisActor`.
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::mem;
+
+use actix::prelude::*;
+use actix_utils::oneshot;
+use backoff::backoff::Backoff;
+use backoff::ExponentialBackoff;
+use futures::{future, FutureExt};
+use redis_async::error::Error as RespError;
+use redis_async::resp::{RespCodec, RespValue};
+use tokio::io::{split, WriteHalf};
+use tokio_util::codec::FramedRead;
+
+use crate::command::ConfigSet;
+use crate::keyspace::{KeyspaceEvent, KeyspaceListener, SubscribeKeyspace};
+pub use crate::listener::Listener;
+use crate::redis::{self, ConnectOptions};
+use crate::resp3::{Resp3Codec, Resp3Value};
+use crate::transport::Stream;
+use crate::Error;
+
+/// Message published to a channel
+#[derive(Debug, Clone)]
+pub struct PubSubMessage {
+    pub channel: String,
+    /// Pattern matching `channel` for the subscriptions made with `PSubscribe`
+    pub pattern: Option<String>,
+    pub payload: Vec<u8>,
+}
+
+impl Message for PubSubMessage {
+    type Result = ();
+}
+
+/// Receiver of the messages of a pattern
+#[derive(Debug, Clone)]
+enum PatternListener {
+    Messages(Listener<PubSubMessage>),
+    /// Receive the messages parsed as keyspace events, dropping other messages
+    Keyspace(KeyspaceListener),
+}
+
+impl PatternListener {
+    /// Deliver `msg`. Returns false if the receiver is gone.
+    fn deliver(&self, msg: PubSubMessage) -> bool {
+        match self {
+            PatternListener::Messages(listener) => listener.deliver(msg),
+            PatternListener::Keyspace(listener) => {
+                match KeyspaceEvent::from_message(&msg) {
+                    Some(event) => listener.deliver(event),
+                    None => true,
+                }
+            }
+        }
+    }
+}
+
+/// Subscribe to `channels`, delivering their messages to `listener`
+#[derive(Debug)]
+pub struct Subscribe {
+    pub channels: Vec<String>,
+    pub listener: Listener<PubSubMessage>,
+}
+
+impl Message for Subscribe {
+    type Result = Result<(), Error>;
+}
+
+/// Subscribe to the channels matching `patterns`, delivering their messages to `listener`
+#[derive(Debug)]
+pub struct PSubscribe {
+    pub patterns: Vec<String>,
+    pub listener: Listener<PubSubMessage>,
+}
+
+impl Message for PSubscribe {
+    type Result = Result<(), Error>;
+}
+
+/// Unsubscribe from `channels`, or from all channels if empty
+#[derive(Debug)]
+pub struct Unsubscribe {
+    pub channels: Vec<String>,
+}
+
+impl Message for Unsubscribe {
+    type Result = Result<(), Error>;
+}
+
+/// Unsubscribe from `patterns`, or from all patterns if empty
+#[derive(Debug)]
+pub struct PUnsubscribe {
+    pub patterns: Vec<String>,
+}
+
+impl Message for PUnsubscribe {
+    type Result = Result<(), Error>;
+}
+
+/// Subscribe to the shard `channels`, delivering their messages to `listener`.
+///
+/// All channels must hash to the same slot, which the server has to own.
+/// `RedisClusterActor` routes the channels to their owners by itself.
+#[derive(Debug)]
+pub struct SSubscribe {
+    pub channels: Vec<String>,
+    pub listener: Listener<PubSubMessage>,
+}
+
+impl Message for SSubscribe {
+    type Result = Result<(), Error>;
+}
+
+/// Unsubscribe from the shard `channels`, or from all shard channels if empty
+#[derive(Debug)]
+pub struct SUnsubscribe {
+    pub channels: Vec<String>,
+}
+
+impl Message for SUnsubscribe {
+    type Result = Result<(), Error>;
+}
+
+/// Shard channel dropped by the server, e.g. because its slot migrated
+#[doc(hidden)]
+pub struct ShardUnsubscribed {
+    pub channel: String,
+    pub listeners: Vec<Listener<PubSubMessage>>,
+}
+
+impl Message for ShardUnsubscribed {
+    type Result = ();
+}
+
+/// Request waiting for the confirmation of each of its channels
+struct Pending {
+    remaining: usize,
+    tx: Option<oneshot::Sender<Result<(), Error>>>,
+}
+
+/// Redis Pub/Sub actor
+///
+/// Subscriptions are made again every time the connection is re-established.
+/// Channels and patterns subscribed while disconnected are subscribed once
+/// the connection is established.
+pub struct RedisSubscriber {
+    addr: String,
+    /// Servers to try in turn when `addr` can not be reached
+    others: VecDeque<String>,
+    options: ConnectOptions,
+    backoff: ExponentialBackoff,
+    cell: Option<actix::io::FramedWrite<WriteHalf<Stream>, RespCodec>>,
+    pending: VecDeque<Pending>,
+    channels: HashMap<String, Vec<Listener<PubSubMessage>>>,
+    patterns: HashMap<String, Vec<PatternListener>>,
+    shard_channels: HashMap<String, Vec<Listener<PubSubMessage>>>,
+    /// Value of `notify-keyspace-events` requested with `SubscribeKeyspace`
+    keyspace_config: Option<String>,
+    /// Notified of the shard channels dropped by the server
+    unsubscribed: Option<Recipient<ShardUnsubscribed>>,
+}
+
+impl RedisSubscriber {
+    /// Start new `Supervisor` with `RedisSubscriber`.
+    pub fn start<S: Into<String>>(addr: S) -> Addr<RedisSubscriber> {
+        Self::start_with(addr, ConnectOptions::default())
+    }
+
+    /// Start new `Supervisor` with `RedisSubscriber` using the given connection options.
+    pub fn start_with<S: Into<String>>(
+        addr: S,
+        options: ConnectOptions,
+    ) -> Addr<RedisSubscriber> {
+        Self::start_inner(addr.into(), vec![], options, None)
+    }
+
+    /// Start a subscriber of the first of `addrs` it can reach, moving on to
+    /// the next one whenever connecting fails
+    pub(crate) fn start_any(
+        mut addrs: Vec<String>,
+        options: ConnectOptions,
+    ) -> Addr<RedisSubscriber> {
+        let addr = addrs.remove(0);
+        Self::start_inner(addr, addrs, options, None)
+    }
+
+    /// Start a subscriber of a cluster node, which reports the shard channels
+    /// dropped by the node to `unsubscribed`
+    pub(crate) fn start_node(
+        addr: String,
+        options: ConnectOptions,
+        unsubscribed: Recipient<ShardUnsubscribed>,
+    ) -> Addr<RedisSubscriber> {
+        Self::start_inner(addr, vec![], options, Some(unsubscribed))
+    }
+
+    fn start_inner(
+        addr: String,
+        others: Vec<String>,
+        options: ConnectOptions,
+        unsubscribed: Option<Recipient<ShardUnsubscribed>>,
+    ) -> Addr<RedisSubscriber> {
+        let mut backoff = ExponentialBackoff {
+            max_elapsed_time: None,
+            ..ExponentialBackoff::default()
+        };
+        backoff.reset();
+
+        Supervisor::start(move |_| RedisSubscriber {
+            addr,
+            others: others.into(),
+            options,
+            backoff,
+            cell: None,
+            pending: VecDeque::new(),
+            channels: HashMap::new(),
+            patterns: HashMap::new(),
+            shard_channels: HashMap::new(),
+            keyspace_config: None,
+            unsubscribed,
+        })
+    }
+
+    /// Send `cmd` for `names`, replying once all of them are confirmed
+    fn request(
+        &mut self,
+        cmd: &str,
+        names: Vec<String>,
+    ) -> ResponseFuture<Result<(), Error>> {
+        let (tx, rx) = oneshot::channel();
+        if names.is_empty() {
+            let _ = tx.send(Ok(()));
+        } else if self.cell.is_some() {
+            self.write(cmd, names, Some(tx));
+        } else {
+            let _ = tx.send(Err(Error::NotConnected));
+        }
+
+        Box::pin(rx.map(|res| match res {
+            Ok(res) => res,
+            Err(_) => Err(Error::Disconnected),
+        }))
+    }
+
+    /// Send `cmd` for each of `names` separately, as shard channels of a single
+    /// command have to share the slot
+    fn request_each(
+        &mut self,
+        cmd: &str,
+        mut names: Vec<String>,
+    ) -> ResponseFuture<Result<(), Error>> {
+        let last = match names.pop() {
+            Some(last) => last,
+            None => return self.request(cmd, names),
+        };
+        if self.cell.is_some() {
+            for name in names {
+                self.write(cmd, vec![name], None);
+            }
+        }
+        self.request(cmd, vec![last])
+    }
+
+    fn write(
+        &mut self,
+        cmd: &str,
+        names: Vec<String>,
+        tx: Option<oneshot::Sender<Result<(), Error>>>,
+    ) {
+        if let Some(ref mut cell) = self.cell {
+            self.pending.push_back(Pending {
+                remaining: names.len(),
+                tx,
+            });
+
+            let mut req = vec![RespValue::BulkString(cmd.as_bytes().to_vec())];
+            req.extend(names.into_iter().map(|name| name.into()));
+            cell.write(RespValue::Array(req));
+        }
+    }
+
+    /// Count a confirmation of the oldest request
+    fn confirm(&mut self) {
+        let done = match self.pending.front_mut() {
+            Some(pending) => {
+                pending.remaining = pending.remaining.saturating_sub(1);
+                pending.remaining == 0
+            }
+            None => false,
+        };
+        if done {
+            if let Some(Pending { tx: Some(tx), .. }) = self.pending.pop_front() {
+                let _ = tx.send(Ok(()));
+            }
+        }
+    }
+
+    fn psubscribe(
+        &mut self,
+        patterns: Vec<String>,
+        listener: PatternListener,
+    ) -> ResponseFuture<Result<(), Error>> {
+        for pattern in patterns.iter() {
+            self.patterns
+                .entry(pattern.clone())
+                .or_default()
+                .push(listener.clone());
+        }
+        if self.cell.is_none() {
+            return Box::pin(future::ok(()));
+        }
+        self.request("PSUBSCRIBE", patterns)
+    }
+
+    /// Deliver `msg` to the listeners, forgetting the ones which are gone
+    fn deliver(
+        listeners: Option<&mut Vec<Listener<PubSubMessage>>>,
+        msg: PubSubMessage,
+    ) {
+        if let Some(listeners) = listeners {
+            listeners.retain(|listener| listener.deliver(msg.clone()));
+        }
+    }
+}
+
+/// Set `notify-keyspace-events` to `value`. A subscribed connection can not
+/// run CONFIG SET, so a separate connection is used.
+async fn notify_keyspace_events(
+    addr: String,
+    options: ConnectOptions,
+    value: String,
+) -> Result<(), Error> {
+    let config = ConfigSet {
+        parameter: "notify-keyspace-events".into(),
+        value,
+    };
+    redis::command(addr, options, config).await
+}
+
+/// Convert the message of a subscribed connection into its parts
+fn parts(value: Resp3Value) -> Option<Vec<Vec<u8>>> {
+    let values = match value {
+        Resp3Value::Array(values) | Resp3Value::Push(values) => values,
+        _ => return None,
+    };
+    values
+        .into_iter()
+        .map(|value| match value {
+            Resp3Value::BulkString(s) => Some(s),
+            Resp3Value::SimpleString(s) => Some(s.into_bytes()),
+            Resp3Value::Integer(i) => Some(i.to_string().into_bytes()),
+            Resp3Value::Nil => Some(vec![]),
+            _ => None,
+        })
+        .collect()
+}
+
+fn utf8(bytes: Vec<u8>) -> String {
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+impl Actor for RedisSubscriber {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Context<Self>) {
+        let addr = self.addr.clone();
+        let options = self.options.clone();
+        let config = self.keyspace_config.clone();
+        let connect = async move {
+            let stream = redis::connect(addr.clone(), options.clone()).await?;
+            // a restarted or failed over server may not publish the
+            // notifications, set it up before subscribing again
+            if let Some(value) = config {
+                if let Err(e) = notify_keyspace_events(addr, options, value).await {
+                    warn!("Can not set notify-keyspace-events: {}", e);
+                }
+            }
+            Ok::<_, Error>(stream)
+        };
+
+        connect
+            .into_actor(self)
+            .map(|res, act, ctx| match res {
+                Ok(stream) => {
+                    info!("Connected to redis server: {}", act.addr);
+
+                    let (r, w) = split(stream);
+                    act.cell = Some(actix::io::FramedWrite::new(w, RespCodec, ctx));
+                    ctx.add_stream(FramedRead::new(r, Resp3Codec));
+
+                    act.backoff.reset();
+
+                    // subscribe again after reconnecting
+                    let channels = act.channels.keys().cloned().collect();
+                    if !act.channels.is_empty() {
+                        act.write("SUBSCRIBE", channels, None);
+                    }
+                    let patterns = act.patterns.keys().cloned().collect();
+                    if !act.patterns.is_empty() {
+                        act.write("PSUBSCRIBE", patterns, None);
+                    }
+                    // shard channels of a single command must share the slot
+                    let shard_channels: Vec<_> =
+                        act.shard_channels.keys().cloned().collect();
+                    for channel in shard_channels {
+                        act.write("SSUBSCRIBE", vec![channel], None);
+                    }
+                }
+                Err(err) => {
+                    error!("Can not connect to redis server: {}", err);
+                    if let Some(next) = act.others.pop_front() {
+                        let addr = mem::replace(&mut act.addr, next);
+                        act.others.push_back(addr);
+                    }
+                    // re-connect with backoff time.
+                    // we stop current context, supervisor will restart it.
+                    if let Some(timeout) = act.backoff.next_backoff() {
+                        ctx.run_later(timeout, |_, ctx| ctx.stop());
+                    }
+                }
+            })
+            .wait(ctx);
+    }
+}
+
+impl Supervised for RedisSubscriber {
+    fn restarting(&mut self, _: &mut Self::Context) {
+        log::info!("Restarting connection to {}", self.addr);
+
+        self.cell.take();
+        for pending in self.pending.drain(..) {
+            if let Some(tx) = pending.tx {
+                let _ = tx.send(Err(Error::Disconnected));
+            }
+        }
+    }
+}
+
+impl actix::io::WriteHandler<io::Error> for RedisSubscriber {
+    fn error(&mut self, err: io::Error, _: &mut Self::Context) -> Running {
+        warn!("Redis connection dropped: {} error: {}", self.addr, err);
+        Running::Stop
+    }
+}
+
+impl StreamHandler<Result<Resp3Value, RespError>> for RedisSubscriber {
+    fn handle(&mut self, msg: Result<Resp3Value, RespError>, ctx: &mut Self::Context) {
+        let msg = match msg {
+            Ok(Resp3Value::Error(e)) => {
+                warn!("Redis subscription failed: {}", e);
+                if let Some(Pending { tx: Some(tx), .. }) = self.pending.pop_front() {
+                    let _ = tx.send(Err(Error::Redis(RespError::Remote(e))));
+                }
+                return;
+            }
+            Ok(msg) => msg,
+            Err(e) => {
+                error!("Redis connection error: {} error: {}", self.addr, e);
+                ctx.stop();
+                return;
+            }
+        };
+
+        let mut parts = match parts(msg) {
+            Some(parts) => parts.into_iter(),
+            None => return,
+        };
+        let kind = parts.next().map(utf8).unwrap_or_default();
+        match (kind.as_str(), parts.next(), parts.next(), parts.next()) {
+            ("message", Some(channel), Some(payload), None) => {
+                let channel = utf8(channel);
+                let listeners = self.channels.get_mut(&channel);
+                let msg = PubSubMessage {
+                    channel,
+                    pattern: None,
+                    payload,
+                };
+                Self::deliver(listeners, msg);
+            }
+            ("pmessage", Some(pattern), Some(channel), Some(payload)) => {
+                let pattern = utf8(pattern);
+                let listeners = self.patterns.get_mut(&pattern);
+                let msg = PubSubMessage {
+                    channel: utf8(channel),
+                    pattern: Some(pattern),
+                    payload,
+                };
+                if let Some(listeners) = listeners {
+                    listeners.retain(|listener| listener.deliver(msg.clone()));
+                }
+            }
+            ("smessage", Some(channel), Some(payload), None) => {
+                let channel = utf8(channel);
+                let listeners = self.shard_channels.get_mut(&channel);
+                let msg = PubSubMessage {
+                    channel,
+                    pattern: None,
+                    payload,
+                };
+                Self::deliver(listeners, msg);
+            }
+            ("sunsubscribe", Some(channel), ..)
+                if self.shard_channels.contains_key(&utf8(channel.clone())) =>
+            {
+                // not requested by us, the server dropped the channel
+                let channel = utf8(channel);
+                warn!("Redis dropped subscription of shard channel {}", channel);
+                let listeners = self.shard_channels.remove(&channel).unwrap_or_default();
+                if let Some(ref unsubscribed) = self.unsubscribed {
+                    let _ =
+                        unsubscribed.do_send(ShardUnsubscribed { channel, listeners });
+                }
+            }
+            ("subscribe", ..)
+            | ("psubscribe", ..)
+            | ("ssubscribe", ..)
+            | ("unsubscribe", ..)
+            | ("punsubscribe", ..)
+            | ("sunsubscribe", ..) => self.confirm(),
+            _ => debug!("Unexpected message on subscribed connection: {}", kind),
+        }
+    }
+}
+
+impl Handler<Subscribe> for RedisSubscriber {
+    type Result = ResponseFuture<Result<(), Error>>;
+
+    fn handle(&mut self, msg: Subscribe, _: &mut Self::Context) -> Self::Result {
+        for channel in msg.channels.iter() {
+            self.channels
+                .entry(channel.clone())
+                .or_default()
+                .push(msg.listener.clone());
+        }
+        if self.cell.is_none() {
+            // subscribed once connected
+            return Box::pin(future::ok(()));
+        }
+        self.request("SUBSCRIBE", msg.channels)
+    }
+}
+
+impl Handler<PSubscribe> for RedisSubscriber {
+    type Result = ResponseFuture<Result<(), Error>>;
+
+    fn handle(&mut self, msg: PSubscribe, _: &mut Self::Context) -> Self::Result {
+        self.psubscribe(msg.patterns, PatternListener::Messages(msg.listener))
+    }
+}
+
+impl Handler<Unsubscribe> for RedisSubscriber {
+    type Result = ResponseFuture<Result<(), Error>>;
+
+    fn handle(&mut self, msg: Unsubscribe, _: &mut Self::Context) -> Self::Result {
+        let channels = if msg.channels.is_empty() {
+            self.channels.drain().map(|(channel, _)| channel).collect()
+        } else {
+            for channel in msg.channels.iter() {
+                self.channels.remove(channel);
+            }
+            msg.channels
+        };
+        self.request("UNSUBSCRIBE", channels)
+    }
+}
+
+impl Handler<PUnsubscribe> for RedisSubscriber {
+    type Result = ResponseFuture<Result<(), Error>>;
+
+    fn handle(&mut self, msg: PUnsubscribe, _: &mut Self::Context) -> Self::Result {
+        let patterns = if msg.patterns.is_empty() {
+            self.patterns.drain().map(|(pattern, _)| pattern).collect()
+        } else {
+            for pattern in msg.patterns.iter() {
+                self.patterns.remove(pattern);
+            }
+            msg.patterns
+        };
+        self.request("PUNSUBSCRIBE", patterns)
+    }
+}
+
+impl Handler<SSubscribe> for RedisSubscriber {
+    type Result = ResponseActFuture<Self, Result<(), Error>>;
+
+    fn handle(&mut self, msg: SSubscribe, _: &mut Self::Context) -> Self::Result {
+        let mut created = vec![];
+        if self.cell.is_some() {
+            for channel in msg.channels.iter() {
+                if !self.shard_channels.contains_key(channel) {
+                    created.push(channel.clone());
+                }
+                self.shard_channels
+                    .entry(channel.clone())
+                    .or_default()
+                    .push(msg.listener.clone());
+            }
+        }
+
+        Box::new(
+            self.request("SSUBSCRIBE", msg.channels)
+                .into_actor(self)
+                .map(move |res, act, _| {
+                    // e.g. MOVED, do not resubscribe after reconnecting
+                    if res.is_err() {
+                        for channel in created.iter() {
+                            act.shard_channels.remove(channel);
+                        }
+                    }
+                    res
+                }),
+        )
+    }
+}
+
+impl Handler<SUnsubscribe> for RedisSubscriber {
+    type Result = ResponseFuture<Result<(), Error>>;
+
+    fn handle(&mut self, msg: SUnsubscribe, _: &mut Self::Context) -> Self::Result {
+        let channels = if msg.channels.is_empty() {
+            self.shard_channels
+                .drain()
+                .map(|(channel, _)| channel)
+                .collect()
+        } else {
+            for channel in msg.channels.iter() {
+                self.shard_channels.remove(channel);
+            }
+            msg.channels
+        };
+        self.request_each("SUNSUBSCRIBE", channels)
+    }
+}
+
+impl Handler<SubscribeKeyspace> for RedisSubscriber {
+    type Result = ResponseActFuture<Self, Result<(), Error>>;
+
+    fn handle(&mut self, msg: SubscribeKeyspace, _: &mut Self::Context) -> Self::Result {
+        let pattern = msg.channel_pattern();
+        let listener = PatternListener::Keyspace(msg.listener);
+
+        let addr = self.addr.clone();
+        let options = self.options.clone();
+        let config = msg.config;
+        let value = config.clone();
+        let set_config = async move {
+            match value {
+                Some(value) => notify_keyspace_events(addr, options, value).await,
+                None => Ok(()),
+            }
+        };
+
+        Box::new(set_config.into_actor(self).then(
+            move |res, act, _| -> ResponseActFuture<Self, Result<(), Error>> {
+                match res {
+                    Ok(()) => {
+                        // set again after reconnecting
+                        if config.is_some() {
+                            act.keyspace_config = config;
+                        }
+                        Box::new(act.psubscribe(vec![pattern], listener).into_actor(act))
+                    }
+                    Err(e) => Box::new(actix::fut::err(e)),
+                }
+            },
+        ))
+    }
+}
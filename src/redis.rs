@@ -1,19 +1,27 @@
 use std::collections::VecDeque;
 use std::io;
+use std::time::{Duration, Instant};
 
-use actix::actors::resolver::{Connect, Resolver};
 use actix::prelude::*;
 use actix_utils::oneshot;
 use backoff::backoff::Backoff;
 use backoff::ExponentialBackoff;
-use futures::FutureExt;
+use futures::future;
+use futures::{FutureExt, SinkExt, StreamExt};
 use redis_async::error::Error as RespError;
 use redis_async::resp::{RespCodec, RespValue};
-use tokio::io::{split, WriteHalf};
-use tokio::net::TcpStream;
-use tokio_util::codec::FramedRead;
+use tokio::io::{split, AsyncRead, AsyncWrite, WriteHalf};
+use tokio_util::codec::{Framed, FramedRead};
 
+use crate::blocking::{BlockingPool, Cancelable};
 use crate::command;
+use crate::listener::Listener;
+use crate::pipeline::Pipeline;
+use crate::pubsub::RedisSubscriber;
+use crate::resp3::{Resp3Codec, Resp3Value};
+use crate::sentinel::{Sentinel, SwitchMaster};
+use crate::transaction::{self, CheckAndSet, Replies, Transaction};
+use crate::transport::{self, Stream};
 use crate::Error;
 
 /// Command for send data to Redis
@@ -24,69 +32,979 @@ impl Message for Command {
     type Result = Result<RespValue, Error>;
 }
 
+/// Command for send data to Redis, replying with the RESP3 value.
+///
+/// Replies are RESP2 values converted to `Resp3Value` unless
+/// `ConnectOptions::resp3` is set.
+#[derive(Debug)]
+pub struct Resp3Command(pub RespValue);
+
+impl Message for Resp3Command {
+    type Result = Result<Resp3Value, Error>;
+}
+
+/// Send a blocking command over a connection of its own. `timeout` is how long
+/// the server may block, see `command::Command::blocking_timeout`.
+#[derive(Debug)]
+pub struct Blocking {
+    pub request: RespValue,
+    pub timeout: Duration,
+    /// Precede the request with `ASKING` over the same connection, after an
+    /// ASK redirection of a cluster
+    pub asking: bool,
+}
+
+impl Message for Blocking {
+    type Result = Result<RespValue, Error>;
+}
+
+/// Out-of-band data pushed by the server over a RESP3 connection
+#[derive(Debug)]
+pub struct Push(pub Vec<Resp3Value>);
+
+impl Message for Push {
+    type Result = ();
+}
+
+/// Send a command with a response timeout overriding the default one of `RedisActor`
+#[derive(Debug)]
+pub struct WithTimeout<M> {
+    pub message: M,
+    pub timeout: Duration,
+}
+
+impl<M> WithTimeout<M> {
+    pub fn new(message: M, timeout: Duration) -> Self {
+        WithTimeout { message, timeout }
+    }
+}
+
+impl<M: Message> Message for WithTimeout<M> {
+    type Result = M::Result;
+}
+
+/// Change of the connection state of `RedisActor`
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionEvent {
+    Connected {
+        addr: String,
+    },
+    /// The connection was lost or could not be established
+    Disconnected {
+        addr: String,
+        error: String,
+    },
+    /// The next connection attempt is made after `delay`
+    Reconnecting {
+        addr: String,
+        delay: Duration,
+    },
+}
+
+impl Message for ConnectionEvent {
+    type Result = ();
+}
+
+/// Receiver of connection events
+pub type ConnectionListener = Listener<ConnectionEvent>;
+
+/// Get the connection state of `RedisActor`, e.g. for readiness probes.
+///
+/// The mailbox is not processed while connecting, so the reply is delayed
+/// until an ongoing connection attempt finishes.
+#[derive(Debug)]
+pub struct Status;
+
+impl Message for Status {
+    type Result = ConnectionStatus;
+}
+
+/// Reply of `Status`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectionStatus {
+    /// Address of the server, empty until the sentinels tell the master
+    pub addr: String,
+    pub state: ConnectionState,
+    /// Number of the requests waiting for their replies
+    pub pending: usize,
+    /// Number of the requests held while reconnecting
+    pub buffered: usize,
+    /// Why the connection was lost or could not be established last time
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConnectionState {
+    /// Connecting or waiting to reconnect
+    Connecting,
+    Connected,
+    /// Gave up reconnecting after `backoff_max_elapsed_time`
+    Disconnected,
+}
+
+/// Stop `RedisActor` gracefully.
+///
+/// New requests fail with `Error::ShuttingDown` right away. The replies to the
+/// requests already written are awaited up to `timeout`, then the connection
+/// is closed. The `Supervisor` does not reconnect afterwards, and the actor
+/// stops once all of its addresses are dropped.
+///
+/// `RedisClusterActor` shuts down the connections to all nodes this way and
+/// stops refreshing the slots.
+#[derive(Debug)]
+pub struct Shutdown {
+    pub timeout: Duration,
+}
+
+impl Message for Shutdown {
+    /// Never fails, the result tells when the connection is closed
+    type Result = Result<(), Error>;
+}
+
+/// What `RedisActor` does with new requests while `max_in_flight` requests
+/// are waiting for their replies
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overload {
+    /// Hold the requests until replies arrive
+    Wait,
+    /// Fail the requests with `Error::Overloaded`
+    FailFast,
+}
+
+/// Options applied every time `RedisActor` (re)connects to the server
+#[derive(Debug, Clone, Default)]
+pub struct ConnectOptions {
+    /// ACL username sent with `AUTH`. Requires `password` to be set.
+    pub username: Option<String>,
+    /// Password sent with `AUTH`
+    pub password: Option<String>,
+    /// Logical database selected with `SELECT`
+    pub db: usize,
+    /// Connection name set with `CLIENT SETNAME`
+    pub client_name: Option<String>,
+    /// Send `READONLY`, allowing reads from a cluster replica
+    pub readonly: bool,
+    /// Give up connecting (including the handshake) after this duration
+    pub connect_timeout: Option<Duration>,
+    /// Set `TCP_NODELAY` on TCP connections
+    pub nodelay: bool,
+    /// Enable TCP keepalive with the given idle time on TCP connections
+    pub keepalive: Option<Duration>,
+    /// Switch to RESP3 with `HELLO 3`, authenticating with it if `password` is set
+    pub resp3: bool,
+    /// Connect over TLS. `rediss://` addresses use the default settings if unset.
+    #[cfg(feature = "tls")]
+    pub tls: Option<transport::TlsConfig>,
+}
+
 /// Redis comminucation actor
 pub struct RedisActor {
+    /// Address of the server, empty until the sentinels tell the master
     addr: String,
+    options: ConnectOptions,
     backoff: ExponentialBackoff,
-    cell: Option<actix::io::FramedWrite<WriteHalf<TcpStream>, RespCodec>>,
-    queue: VecDeque<oneshot::Sender<Result<RespValue, Error>>>,
+    cell: Option<actix::io::FramedWrite<WriteHalf<Stream>, RespCodec>>,
+    queue: VecDeque<Pending>,
+    /// Id of the next request, used to find the waiter of an expired request
+    next_id: u64,
+    response_timeout: Option<Duration>,
+    /// Requests held while reconnecting, oldest first
+    offline: VecDeque<Buffered>,
+    offline_max_len: usize,
+    offline_max_wait: Duration,
+    /// Requests held until fewer than `max_in_flight` replies are pending
+    waiting: VecDeque<Waiting>,
+    max_in_flight: usize,
+    overload: Overload,
+    /// Interval and deadline of the liveness `PING`
+    ping: Option<(Duration, Duration)>,
+    /// Time of the last reply, a connection is idle when it is older than the interval
+    last_read: Instant,
+    /// Receiver of push frames of RESP3 connections
+    push: Option<Recipient<Push>>,
+    /// Reason of the last rejected `AUTH`, reported instead of `NotConnected`
+    auth_error: Option<String>,
+    /// Connections of blocking commands, which would stall the queue
+    blocking: BlockingPool,
+    state: ConnectionState,
+    /// Why the current connection is being dropped
+    drop_reason: Option<String>,
+    last_error: Option<String>,
+    listeners: Vec<ConnectionListener>,
+    shutdown: bool,
+    /// Waiters of `Shutdown`, notified once no reply is pending
+    drained: Vec<oneshot::Sender<()>>,
+    /// Sentinels telling the address of the master before connecting
+    sentinel: Option<Sentinel>,
+    /// Subscription to the failovers announced by the sentinels
+    sentinel_subscriber: Option<Addr<RedisSubscriber>>,
+}
+
+/// Waiter of a reply from the server
+struct Pending {
+    id: u64,
+    tx: oneshot::Sender<Result<Resp3Value, Error>>,
+}
+
+/// Request waiting for a reply to make room for it
+struct Waiting {
+    req: RespValue,
+    timeout: Option<Duration>,
+    tx: oneshot::Sender<Result<Resp3Value, Error>>,
+}
+
+/// Request waiting for the connection to be re-established
+struct Buffered {
+    req: RespValue,
+    timeout: Option<Duration>,
+    deadline: Instant,
+    tx: oneshot::Sender<Result<Resp3Value, Error>>,
 }
 
 impl RedisActor {
     /// Start new `Supervisor` with `RedisActor`.
+    ///
+    /// `addr` is either `host:port`, `redis://host:port`, `rediss://host:port`
+    /// for TLS (requires the `tls` feature) or `unix:///path/to/redis.sock`.
     pub fn start<S: Into<String>>(addr: S) -> Addr<RedisActor> {
-        let addr = addr.into();
+        Self::builder().start(addr)
+    }
+
+    /// Start new `Supervisor` with `RedisActor` using the given connection options.
+    pub fn start_with<S: Into<String>>(
+        addr: S,
+        options: ConnectOptions,
+    ) -> Addr<RedisActor> {
+        Self::builder().options(options).start(addr)
+    }
+
+    /// Start new `Supervisor` with `RedisActor` connecting to the master
+    /// monitored by `sentinel`.
+    pub fn start_sentinel(sentinel: Sentinel) -> Addr<RedisActor> {
+        Self::builder().start_sentinel(sentinel)
+    }
+
+    /// Create a builder to configure `RedisActor` before starting it.
+    pub fn builder() -> RedisActorBuilder {
+        RedisActorBuilder::default()
+    }
+
+    /// Write `req` to the connection and wait for the reply
+    fn send(
+        &mut self,
+        req: RespValue,
+        timeout: Option<Duration>,
+        ctx: &mut Context<Self>,
+    ) -> ResponseFuture<Result<Resp3Value, Error>> {
+        if self.overloaded() {
+            return Box::pin(future::err(Error::Overloaded));
+        }
+
+        let deadline = Instant::now() + self.offline_max_wait;
+        self.send_until(req, timeout, deadline, ctx)
+    }
+
+    /// Write `reqs` back to back, so that no other request comes in between
+    fn send_batch(
+        &mut self,
+        reqs: Vec<RespValue>,
+        timeout: Option<Duration>,
+        ctx: &mut Context<Self>,
+    ) -> ResponseFuture<Result<Vec<Resp3Value>, Error>> {
+        if self.shutdown {
+            return Box::pin(future::err(Error::ShuttingDown));
+        }
+
+        // hold all or none of them while reconnecting
+        if self.cell.is_none()
+            && self.auth_error.is_none()
+            && self.offline.len() + reqs.len() > self.offline_max_len
+        {
+            return Box::pin(future::err(Error::NotConnected));
+        }
+        // the requests beyond the limit wait for the first ones
+        if self.overloaded() {
+            return Box::pin(future::err(Error::Overloaded));
+        }
+
+        // the same deadline expires all of them at once
+        let deadline = Instant::now() + self.offline_max_wait;
+        let replies: Vec<_> = reqs
+            .into_iter()
+            .map(|req| self.send_until(req, timeout, deadline, ctx))
+            .collect();
+        Box::pin(future::join_all(replies).map(|replies| replies.into_iter().collect()))
+    }
+
+    /// Send a blocking command over a connection of its own, preceded by
+    /// `ASKING` if `asking`. The response timeout starts once the server stops
+    /// blocking after `blocking`.
+    fn send_blocking(
+        &self,
+        req: RespValue,
+        blocking: Duration,
+        timeout: Option<Duration>,
+        asking: bool,
+    ) -> ResponseFuture<Result<Resp3Value, Error>> {
+        if self.shutdown {
+            return Box::pin(future::err(Error::ShuttingDown));
+        }
+        if self.addr.is_empty() {
+            return Box::pin(future::err(Error::NotConnected));
+        }
+
+        // zero blocks until a reply is available
+        let timeout = if blocking == Duration::from_secs(0) {
+            None
+        } else {
+            timeout.map(|timeout| timeout + blocking)
+        };
+        self.blocking
+            .request(self.addr.clone(), req, timeout, asking)
+    }
+
+    /// Write `req` to the connection, or hold it until `deadline` while reconnecting
+    fn send_until(
+        &mut self,
+        req: RespValue,
+        timeout: Option<Duration>,
+        deadline: Instant,
+        ctx: &mut Context<Self>,
+    ) -> ResponseFuture<Result<Resp3Value, Error>> {
+        let (tx, rx) = oneshot::channel();
+        if self.shutdown {
+            let _ = tx.send(Err(Error::ShuttingDown));
+        } else if self.cell.is_some() {
+            self.dispatch(req, timeout, tx, ctx);
+        } else if let Some(ref e) = self.auth_error {
+            let _ = tx.send(Err(Error::Auth(e.clone())));
+        } else if self.offline.len() < self.offline_max_len {
+            self.offline.push_back(Buffered {
+                req,
+                timeout,
+                deadline,
+                tx,
+            });
+            if self.offline.len() == 1 {
+                self.schedule_offline_expiry(ctx);
+            }
+        } else {
+            let _ = tx.send(Err(Error::NotConnected));
+        }
+
+        Box::pin(rx.map(|res| match res {
+            Ok(res) => res,
+            Err(_) => Err(Error::Disconnected),
+        }))
+    }
 
-        let mut backoff = ExponentialBackoff::default();
-        backoff.max_elapsed_time = None;
+    /// Whether new requests are refused with `Error::Overloaded`
+    fn overloaded(&self) -> bool {
+        self.overload == Overload::FailFast
+            && self.cell.is_some()
+            && (!self.waiting.is_empty() || self.queue.len() >= self.max_in_flight)
+    }
 
-        Supervisor::start(|_| RedisActor {
+    /// Write `req` to the connection if fewer than `max_in_flight` replies are
+    /// pending, or hold it until there is room
+    fn dispatch(
+        &mut self,
+        req: RespValue,
+        timeout: Option<Duration>,
+        tx: oneshot::Sender<Result<Resp3Value, Error>>,
+        ctx: &mut Context<Self>,
+    ) {
+        if self.waiting.is_empty() && self.queue.len() < self.max_in_flight {
+            self.write(req, timeout, tx, ctx);
+        } else {
+            self.waiting.push_back(Waiting { req, timeout, tx });
+        }
+    }
+
+    /// Write the held requests there is room for
+    fn flush_waiting(&mut self, ctx: &mut Context<Self>) {
+        while self.queue.len() < self.max_in_flight {
+            match self.waiting.pop_front() {
+                Some(waiting) => {
+                    if !waiting.tx.is_canceled() {
+                        self.write(waiting.req, waiting.timeout, waiting.tx, ctx);
+                    }
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn write(
+        &mut self,
+        req: RespValue,
+        timeout: Option<Duration>,
+        tx: oneshot::Sender<Result<Resp3Value, Error>>,
+        ctx: &mut Context<Self>,
+    ) {
+        if let Some(ref mut cell) = self.cell {
+            let id = self.next_id;
+            self.next_id += 1;
+
+            self.queue.push_back(Pending { id, tx });
+            cell.write(req);
+
+            if let Some(timeout) = timeout {
+                ctx.run_later(timeout, move |act, ctx| act.expire(id, ctx));
+            }
+        }
+    }
+
+    /// Send `PING` over idle connections. A connection whose peer silently went
+    /// away fails the `PING` with `Timeout` and gets reset by `expire`.
+    ///
+    /// The `PING` is written even if `max_in_flight` replies are pending, as a
+    /// held one would never be sent by a connection which stopped replying.
+    fn schedule_ping(&mut self, ctx: &mut Context<Self>) {
+        if let Some((interval, timeout)) = self.ping {
+            ctx.run_interval(interval, move |act, ctx| {
+                if act.last_read.elapsed() < interval {
+                    return;
+                }
+                debug!("Checking liveness of redis connection: {}", act.addr);
+                let (tx, _) = oneshot::channel();
+                let ping = command::Command::into_request(command::Ping(None));
+                act.write(ping, Some(timeout), tx, ctx);
+            });
+        }
+    }
+
+    /// Send the requests held while reconnecting
+    fn flush_offline(&mut self, ctx: &mut Context<Self>) {
+        while let Some(buffered) = self.offline.pop_front() {
+            if !buffered.tx.is_canceled() {
+                self.dispatch(buffered.req, buffered.timeout, buffered.tx, ctx);
+            }
+        }
+    }
+
+    /// Fail the oldest held request with `NotConnected` once it waited too long.
+    /// Timers do not survive a restart, so this is called again from `started`.
+    fn schedule_offline_expiry(&mut self, ctx: &mut Context<Self>) {
+        if let Some(buffered) = self.offline.front() {
+            let now = Instant::now();
+            let delay = if buffered.deadline > now {
+                buffered.deadline - now
+            } else {
+                Duration::from_secs(0)
+            };
+            ctx.run_later(delay, |act, ctx| {
+                let now = Instant::now();
+                while let Some(buffered) = act.offline.front() {
+                    if buffered.deadline > now {
+                        break;
+                    }
+                    if let Some(buffered) = act.offline.pop_front() {
+                        let _ = buffered.tx.send(Err(Error::NotConnected));
+                    }
+                }
+                act.schedule_offline_expiry(ctx);
+            });
+        }
+    }
+
+    /// Fail the request `id` with `Timeout` if it is still waiting for the reply
+    fn expire(&mut self, id: u64, ctx: &mut Context<Self>) {
+        // replies are matched to the requests in order, so nothing is pending
+        // when the oldest request is newer
+        match self.queue.front() {
+            Some(pending) if pending.id <= id => (),
+            _ => return,
+        }
+
+        if let Some(pos) = self.queue.iter().position(|p| p.id == id) {
+            warn!("Redis request timed out: {}", self.addr);
+            self.drop_reason = Some(Error::Timeout.to_string());
+            if let Some(pending) = self.queue.remove(pos) {
+                let _ = pending.tx.send(Err(Error::Timeout));
+            }
+            // the late reply would be matched to the next waiter,
+            // so the connection is dropped and the supervisor reconnects.
+            ctx.stop();
+        }
+    }
+
+    /// Switch to the master found by the sentinel at `index`, and watch for
+    /// its failovers unless done already
+    fn use_master(&mut self, index: usize, addr: String, ctx: &mut Context<Self>) {
+        if addr != self.addr {
+            // the idle connections are to the former master
+            self.blocking.clear();
+            self.addr = addr;
+        }
+        if let Some(ref mut sentinel) = self.sentinel {
+            if self.sentinel_subscriber.is_none() {
+                sentinel.prefer(index);
+                self.sentinel_subscriber =
+                    Some(sentinel.watch(ctx.address().downgrade()));
+            }
+        }
+    }
+
+    /// Wake up the waiters of `Shutdown` once no reply is pending
+    fn check_drained(&mut self) {
+        if self.queue.is_empty() {
+            for tx in self.drained.drain(..) {
+                let _ = tx.send(());
+            }
+        }
+    }
+
+    /// Notify the listeners, forgetting the ones which are gone
+    fn notify(&mut self, event: ConnectionEvent) {
+        self.listeners
+            .retain(|listener| listener.deliver(event.clone()));
+    }
+}
+
+/// Builder of `RedisActor`
+#[derive(Debug, Clone)]
+pub struct RedisActorBuilder {
+    options: ConnectOptions,
+    backoff_initial_interval: Duration,
+    backoff_max_interval: Duration,
+    backoff_max_elapsed_time: Option<Duration>,
+    response_timeout: Option<Duration>,
+    offline_max_len: usize,
+    offline_max_wait: Duration,
+    max_in_flight: usize,
+    overload: Overload,
+    ping: Option<(Duration, Duration)>,
+    push: Option<Recipient<Push>>,
+    blocking_max_idle: usize,
+    listeners: Vec<ConnectionListener>,
+}
+
+impl Default for RedisActorBuilder {
+    fn default() -> Self {
+        let backoff = ExponentialBackoff::default();
+
+        RedisActorBuilder {
+            options: ConnectOptions::default(),
+            backoff_initial_interval: backoff.initial_interval,
+            backoff_max_interval: backoff.max_interval,
+            backoff_max_elapsed_time: None,
+            response_timeout: None,
+            offline_max_len: 0,
+            offline_max_wait: Duration::from_secs(0),
+            max_in_flight: std::usize::MAX,
+            overload: Overload::Wait,
+            ping: None,
+            push: None,
+            blocking_max_idle: 4,
+            listeners: vec![],
+        }
+    }
+}
+
+impl RedisActorBuilder {
+    /// Replace all connection options at once
+    pub fn options(mut self, options: ConnectOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set the ACL username sent with `AUTH`
+    pub fn username<S: Into<String>>(mut self, username: S) -> Self {
+        self.options.username = Some(username.into());
+        self
+    }
+
+    /// Set the password sent with `AUTH`
+    pub fn password<S: Into<String>>(mut self, password: S) -> Self {
+        self.options.password = Some(password.into());
+        self
+    }
+
+    /// Set the logical database selected after connecting
+    pub fn db(mut self, db: usize) -> Self {
+        self.options.db = db;
+        self
+    }
+
+    /// Set the connection name with `CLIENT SETNAME` after connecting
+    pub fn client_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.options.client_name = Some(name.into());
+        self
+    }
+
+    /// Set timeout of establishing a connection, including the handshake
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.options.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Set `TCP_NODELAY` on TCP connections
+    pub fn nodelay(mut self, nodelay: bool) -> Self {
+        self.options.nodelay = nodelay;
+        self
+    }
+
+    /// Enable TCP keepalive with the given idle time on TCP connections
+    pub fn keepalive(mut self, keepalive: Option<Duration>) -> Self {
+        self.options.keepalive = keepalive;
+        self
+    }
+
+    /// Switch to RESP3 after connecting
+    pub fn resp3(mut self, resp3: bool) -> Self {
+        self.options.resp3 = resp3;
+        self
+    }
+
+    /// Send push frames of RESP3 connections to `recipient`
+    pub fn push_recipient(mut self, recipient: Recipient<Push>) -> Self {
+        self.push = Some(recipient);
+        self
+    }
+
+    /// Send the changes of the connection state to `listener`.
+    /// Can be called multiple times to add more listeners.
+    pub fn connection_listener<L: Into<ConnectionListener>>(
+        mut self,
+        listener: L,
+    ) -> Self {
+        self.listeners.push(listener.into());
+        self
+    }
+
+    /// Connect over TLS
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, tls: transport::TlsConfig) -> Self {
+        self.options.tls = Some(tls);
+        self
+    }
+
+    /// Set the first delay before reconnecting
+    pub fn backoff_initial_interval(mut self, interval: Duration) -> Self {
+        self.backoff_initial_interval = interval;
+        self
+    }
+
+    /// Set the upper bound of the delay between reconnection attempts
+    pub fn backoff_max_interval(mut self, interval: Duration) -> Self {
+        self.backoff_max_interval = interval;
+        self
+    }
+
+    /// Stop reconnecting after the given duration of consecutive failures.
+    /// `None` (the default) reconnects forever.
+    pub fn backoff_max_elapsed_time(mut self, elapsed: Option<Duration>) -> Self {
+        self.backoff_max_elapsed_time = elapsed;
+        self
+    }
+
+    /// Fail requests with `Error::Timeout` when no reply arrives within `timeout`.
+    /// The connection is reset afterwards. Can be overridden with `WithTimeout`.
+    pub fn response_timeout(mut self, timeout: Duration) -> Self {
+        self.response_timeout = Some(timeout);
+        self
+    }
+
+    /// Hold up to `max_len` requests while reconnecting instead of failing them
+    /// with `Error::NotConnected` right away. They are sent once the connection
+    /// is re-established, or fail with `Error::NotConnected` after `max_wait`.
+    pub fn offline_queue(mut self, max_len: usize, max_wait: Duration) -> Self {
+        self.offline_max_len = max_len;
+        self.offline_max_wait = max_wait;
+        self
+    }
+
+    /// Limit the requests waiting for their replies to `max`, so a slow server
+    /// does not make the write buffer grow without bound. `overload` decides
+    /// what happens to the requests beyond the limit. Unlimited by default.
+    pub fn max_in_flight(mut self, max: usize, overload: Overload) -> Self {
+        self.max_in_flight = max.max(1);
+        self.overload = overload;
+        self
+    }
+
+    /// Send `PING` when nothing was received for `interval`, and reconnect when
+    /// its reply does not arrive within `timeout`
+    pub fn ping_interval(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.ping = Some((interval, timeout));
+        self
+    }
+
+    /// Keep up to `max_idle` connections of blocking commands open after
+    /// their replies. Default is 4.
+    pub fn blocking_max_idle(mut self, max_idle: usize) -> Self {
+        self.blocking_max_idle = max_idle;
+        self
+    }
+
+    pub(crate) fn connect_options(&self) -> &ConnectOptions {
+        &self.options
+    }
+
+    pub(crate) fn get_response_timeout(&self) -> Option<Duration> {
+        self.response_timeout
+    }
+
+    /// Start new `Supervisor` with `RedisActor` connecting to `addr`.
+    pub fn start<S: Into<String>>(&self, addr: S) -> Addr<RedisActor> {
+        self.start_inner(addr.into(), None)
+    }
+
+    /// Start new `Supervisor` with `RedisActor` connecting to the master
+    /// monitored by `sentinel`, looked up again on every reconnection.
+    pub fn start_sentinel(&self, sentinel: Sentinel) -> Addr<RedisActor> {
+        self.start_inner(String::new(), Some(sentinel))
+    }
+
+    fn start_inner(&self, addr: String, sentinel: Option<Sentinel>) -> Addr<RedisActor> {
+        let options = self.options.clone();
+        let response_timeout = self.response_timeout;
+        let offline_max_len = self.offline_max_len;
+        let offline_max_wait = self.offline_max_wait;
+        let max_in_flight = self.max_in_flight;
+        let overload = self.overload;
+        let ping = self.ping;
+        let push = self.push.clone();
+        let blocking_max_idle = self.blocking_max_idle;
+        let listeners = self.listeners.clone();
+
+        let mut backoff = ExponentialBackoff {
+            initial_interval: self.backoff_initial_interval,
+            max_interval: self.backoff_max_interval,
+            max_elapsed_time: self.backoff_max_elapsed_time,
+            ..ExponentialBackoff::default()
+        };
+        backoff.reset();
+
+        Supervisor::start(move |_| RedisActor {
+            blocking: BlockingPool::new(options.clone(), blocking_max_idle),
             addr,
+            options,
             cell: None,
             backoff,
             queue: VecDeque::new(),
+            next_id: 0,
+            response_timeout,
+            offline: VecDeque::new(),
+            offline_max_len,
+            offline_max_wait,
+            waiting: VecDeque::new(),
+            max_in_flight,
+            overload,
+            ping,
+            last_read: Instant::now(),
+            push,
+            auth_error: None,
+            state: ConnectionState::Connecting,
+            drop_reason: None,
+            last_error: None,
+            listeners,
+            shutdown: false,
+            drained: vec![],
+            sentinel,
+            sentinel_subscriber: None,
         })
     }
 }
 
+/// Send a single command over a connection which is not served by `RedisActor` yet
+pub(crate) async fn request<S, C>(
+    framed: &mut Framed<S, Resp3Codec>,
+    cmd: C,
+) -> Result<C::Output, Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    C: command::Command,
+{
+    let res = roundtrip(framed, cmd.into_request()).await?;
+    C::from_response(res.into_resp2()).map_err(Error::Redis)
+}
+
+/// Send `req` over a connection which is not served by `RedisActor` and wait for the reply
+pub(crate) async fn roundtrip<S>(
+    framed: &mut Framed<S, Resp3Codec>,
+    req: RespValue,
+) -> Result<Resp3Value, Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    framed.send(req).await?;
+    match framed.next().await {
+        Some(Ok(res)) => Ok(res),
+        Some(Err(e)) => Err(e.into()),
+        None => Err(Error::Disconnected),
+    }
+}
+
+/// Prepare a freshly opened connection before any queued command is sent over it
+async fn handshake<S>(stream: S, options: &ConnectOptions) -> Result<S, Error>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut framed = Framed::new(stream, Resp3Codec);
+
+    if options.resp3 {
+        let auth = options.password.clone().map(|password| {
+            let username = options.username.clone();
+            (username.unwrap_or_else(|| "default".to_string()), password)
+        });
+        let authenticate = auth.is_some();
+        let hello = command::Hello { protover: 3, auth };
+        request(&mut framed, hello).await?.map_err(|e| {
+            if authenticate {
+                Error::Auth(e)
+            } else {
+                Error::Redis(RespError::Remote(e))
+            }
+        })?;
+    } else if let Some(ref password) = options.password {
+        let auth = command::Auth {
+            username: options.username.clone(),
+            password: password.clone(),
+        };
+        request(&mut framed, auth).await?.map_err(Error::Auth)?;
+    }
+
+    if options.db != 0 {
+        request(&mut framed, command::Select { db: options.db }).await?;
+    }
+
+    if let Some(ref name) = options.client_name {
+        let setname = command::ClientSetName { name: name.clone() };
+        request(&mut framed, setname).await?;
+    }
+
+    if options.readonly {
+        request(&mut framed, command::ReadOnly).await?;
+    }
+
+    Ok(framed.into_inner())
+}
+
+/// Open a connection to `addr` and run the handshake, within `connect_timeout`
+pub(crate) async fn connect(
+    addr: String,
+    options: ConnectOptions,
+) -> Result<Stream, Error> {
+    let connect = async {
+        let stream = transport::connect(&addr, &options).await?;
+        handshake(stream, &options).await
+    };
+    match options.connect_timeout {
+        Some(timeout) => {
+            tokio::time::timeout(timeout, connect)
+                .await
+                .unwrap_or_else(|_| {
+                    Err(Error::IoError(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "connection timed out",
+                    )))
+                })
+        }
+        None => connect.await,
+    }
+}
+
+/// Run a single command over a new connection to `addr`
+pub(crate) async fn command<C>(
+    addr: String,
+    options: ConnectOptions,
+    cmd: C,
+) -> Result<C::Output, Error>
+where
+    C: command::Command,
+{
+    let stream = connect(addr, options).await?;
+    request(&mut Framed::new(stream, Resp3Codec), cmd).await
+}
+
 impl Actor for RedisActor {
     type Context = Context<Self>;
 
     fn started(&mut self, ctx: &mut Context<Self>) {
-        Resolver::from_registry()
-            .send(Connect::host(self.addr.as_str()))
+        if self.shutdown {
+            self.state = ConnectionState::Disconnected;
+            return;
+        }
+
+        self.schedule_offline_expiry(ctx);
+
+        let addr = self.addr.clone();
+        let options = self.options.clone();
+        let sentinel = self.sentinel.clone();
+        let connect = async move {
+            // the master may have changed while disconnected
+            let (master, addr) = match sentinel {
+                Some(sentinel) => {
+                    let (index, addr) = sentinel.master_addr().await?;
+                    (Some(index), addr)
+                }
+                None => (None, addr),
+            };
+            let stream = connect(addr.clone(), options).await?;
+            Ok((master, addr, stream))
+        };
+
+        connect
             .into_actor(self)
             .map(|res, act, ctx| match res {
-                Ok(res) => match res {
-                    Ok(stream) => {
-                        info!("Connected to redis server: {}", act.addr);
+                Ok((master, addr, stream)) => {
+                    if let Some(index) = master {
+                        act.use_master(index, addr, ctx);
+                    }
+                    info!("Connected to redis server: {}", act.addr);
 
-                        let (r, w) = split(stream);
+                    let (r, w) = split(stream);
 
-                        // configure write side of the connection
-                        let framed = actix::io::FramedWrite::new(w, RespCodec, ctx);
-                        act.cell = Some(framed);
+                    // configure write side of the connection
+                    let framed = actix::io::FramedWrite::new(w, RespCodec, ctx);
+                    act.cell = Some(framed);
 
-                        // read side of the connection
+                    // read side of the connection
+                    if act.options.resp3 {
+                        ctx.add_stream(FramedRead::new(r, Resp3Codec));
+                    } else {
                         ctx.add_stream(FramedRead::new(r, RespCodec));
-
-                        act.backoff.reset();
                     }
-                    Err(err) => {
-                        error!("Can not connect to redis server: {}", err);
-                        // re-connect with backoff time.
-                        // we stop current context, supervisor will restart it.
-                        if let Some(timeout) = act.backoff.next_backoff() {
-                            ctx.run_later(timeout, |_, ctx| ctx.stop());
-                        }
-                    }
-                },
+
+                    act.backoff.reset();
+                    act.auth_error = None;
+                    act.last_read = Instant::now();
+                    act.schedule_ping(ctx);
+
+                    act.state = ConnectionState::Connected;
+                    act.notify(ConnectionEvent::Connected {
+                        addr: act.addr.clone(),
+                    });
+
+                    act.flush_offline(ctx);
+                }
                 Err(err) => {
                     error!("Can not connect to redis server: {}", err);
+                    if let Error::Auth(ref e) = err {
+                        act.auth_error = Some(e.clone());
+                        // the credentials will not get better by waiting
+                        for buffered in act.offline.drain(..) {
+                            let _ = buffered.tx.send(Err(Error::Auth(e.clone())));
+                        }
+                    }
+                    act.last_error = Some(err.to_string());
+                    act.notify(ConnectionEvent::Disconnected {
+                        addr: act.addr.clone(),
+                        error: err.to_string(),
+                    });
+
                     // re-connect with backoff time.
                     // we stop current context, supervisor will restart it.
                     if let Some(timeout) = act.backoff.next_backoff() {
+                        act.notify(ConnectionEvent::Reconnecting {
+                            addr: act.addr.clone(),
+                            delay: timeout,
+                        });
                         ctx.run_later(timeout, |_, ctx| ctx.stop());
+                    } else {
+                        act.state = ConnectionState::Disconnected;
                     }
                 }
             })
@@ -98,70 +1016,291 @@ impl Supervised for RedisActor {
     fn restarting(&mut self, _: &mut Self::Context) {
         log::info!("Restarting connection to {}", self.addr);
 
-        self.cell.take();
-        for tx in self.queue.drain(..) {
-            let _ = tx.send(Err(Error::Disconnected));
+        if self.cell.take().is_some() {
+            let error = self
+                .drop_reason
+                .take()
+                .unwrap_or_else(|| Error::Disconnected.to_string());
+            self.last_error = Some(error.clone());
+            self.state = ConnectionState::Connecting;
+            self.notify(ConnectionEvent::Disconnected {
+                addr: self.addr.clone(),
+                error,
+            });
+            if !self.shutdown {
+                self.notify(ConnectionEvent::Reconnecting {
+                    addr: self.addr.clone(),
+                    delay: Duration::from_secs(0),
+                });
+            }
         }
+        for pending in self.queue.drain(..) {
+            let _ = pending.tx.send(Err(Error::Disconnected));
+        }
+        // never written, so they can not have been executed
+        for waiting in self.waiting.drain(..) {
+            let _ = waiting.tx.send(Err(Error::NotConnected));
+        }
+        self.check_drained();
     }
 }
 
 impl actix::io::WriteHandler<io::Error> for RedisActor {
     fn error(&mut self, err: io::Error, _: &mut Self::Context) -> Running {
         warn!("Redis connection dropped: {} error: {}", self.addr, err);
+        self.drop_reason = Some(Error::IoError(err).to_string());
         Running::Stop
     }
 }
 
 impl StreamHandler<Result<RespValue, RespError>> for RedisActor {
     fn handle(&mut self, msg: Result<RespValue, RespError>, ctx: &mut Self::Context) {
+        StreamHandler::handle(self, msg.map(Resp3Value::from), ctx)
+    }
+}
+
+impl StreamHandler<Result<Resp3Value, RespError>> for RedisActor {
+    fn handle(&mut self, msg: Result<Resp3Value, RespError>, ctx: &mut Self::Context) {
+        self.last_read = Instant::now();
         match msg {
             Err(e) => {
-                if let Some(tx) = self.queue.pop_front() {
-                    let _ = tx.send(Err(e.into()));
+                let e = Error::from(e);
+                self.drop_reason = Some(e.to_string());
+                if let Some(pending) = self.queue.pop_front() {
+                    let _ = pending.tx.send(Err(e));
                 }
                 ctx.stop();
             }
+            Ok(Resp3Value::Push(data)) => {
+                if let Some(ref push) = self.push {
+                    let _ = push.do_send(Push(data));
+                }
+            }
             Ok(val) => {
-                if let Some(tx) = self.queue.pop_front() {
-                    let _ = tx.send(Ok(val));
+                if let Some(pending) = self.queue.pop_front() {
+                    let _ = pending.tx.send(Ok(val));
+                }
+                self.flush_waiting(ctx);
+                if self.shutdown {
+                    self.check_drained();
                 }
             }
         }
     }
 }
 
+impl Handler<SwitchMaster> for RedisActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: SwitchMaster, ctx: &mut Self::Context) {
+        if msg.addr == self.addr {
+            return;
+        }
+        info!("Redis master switched from {} to {}", self.addr, msg.addr);
+        // the supervisor reconnects, asking the sentinels for the new master
+        if self.cell.is_some() {
+            self.drop_reason = Some(format!("master switched to {}", msg.addr));
+            ctx.stop();
+        }
+    }
+}
+
+impl Handler<Status> for RedisActor {
+    type Result = MessageResult<Status>;
+
+    fn handle(&mut self, _: Status, _: &mut Self::Context) -> Self::Result {
+        MessageResult(ConnectionStatus {
+            addr: self.addr.clone(),
+            state: self.state,
+            pending: self.queue.len(),
+            buffered: self.offline.len(),
+            last_error: self.last_error.clone(),
+        })
+    }
+}
+
+impl Handler<Shutdown> for RedisActor {
+    type Result = ResponseFuture<Result<(), Error>>;
+
+    fn handle(&mut self, msg: Shutdown, ctx: &mut Self::Context) -> Self::Result {
+        if !self.shutdown {
+            info!("Shutting down connection to {}", self.addr);
+            self.shutdown = true;
+            for buffered in self.offline.drain(..) {
+                let _ = buffered.tx.send(Err(Error::ShuttingDown));
+            }
+            for waiting in self.waiting.drain(..) {
+                let _ = waiting.tx.send(Err(Error::ShuttingDown));
+            }
+            self.blocking.clear();
+            self.sentinel_subscriber = None;
+        }
+
+        if self.queue.is_empty() || msg.timeout == Duration::from_secs(0) {
+            if self.cell.is_some() {
+                self.drop_reason = Some(Error::ShuttingDown.to_string());
+                ctx.stop();
+            } else {
+                self.state = ConnectionState::Disconnected;
+            }
+            return Box::pin(future::ok(()));
+        }
+
+        // close the connection once the replies arrive or the deadline passes
+        let (tx, rx) = oneshot::channel();
+        self.drained.push(tx);
+        let addr = ctx.address();
+        Box::pin(async move {
+            let deadline = tokio::time::delay_for(msg.timeout);
+            futures::pin_mut!(deadline);
+            let _ = future::select(rx, deadline).await;
+            let _ = addr
+                .send(Shutdown {
+                    timeout: Duration::from_secs(0),
+                })
+                .await;
+            Ok(())
+        })
+    }
+}
+
 impl Handler<Command> for RedisActor {
     type Result = ResponseFuture<Result<RespValue, Error>>;
 
-    fn handle(&mut self, msg: Command, _: &mut Self::Context) -> Self::Result {
-        let (tx, rx) = oneshot::channel();
-        if let Some(ref mut cell) = self.cell {
-            self.queue.push_back(tx);
-            cell.write(msg.0);
-        } else {
-            let _ = tx.send(Err(Error::NotConnected));
-        }
+    fn handle(&mut self, msg: Command, ctx: &mut Self::Context) -> Self::Result {
+        let res = self.send(msg.0, self.response_timeout, ctx);
+        Box::pin(res.map(|res| res.map(Resp3Value::into_resp2)))
+    }
+}
 
-        Box::pin(rx.map(|res| match res {
-            Ok(res) => res,
-            Err(_) => Err(Error::Disconnected),
-        }))
+impl Handler<Resp3Command> for RedisActor {
+    type Result = ResponseFuture<Result<Resp3Value, Error>>;
+
+    fn handle(&mut self, msg: Resp3Command, ctx: &mut Self::Context) -> Self::Result {
+        self.send(msg.0, self.response_timeout, ctx)
     }
 }
 
 impl<M> Handler<M> for RedisActor
 where
     M: command::Command
-        + Message<Result = Result<<M as command::Command>::Output, Error>>,
+        + Message<Result = Result<<M as command::Command>::Output, Error>>
+        + 'static,
     <M as command::Command>::Output: Send + 'static,
 {
-    type Result = ResponseFuture<Result<M::Output, Error>>;
+    type Result = Cancelable<Self, Result<M::Output, Error>>;
 
     fn handle(&mut self, msg: M, ctx: &mut Self::Context) -> Self::Result {
-        Box::pin(
-            Handler::handle(self, Command(msg.into_request()), ctx).map(|res| {
-                res.and_then(|res| M::from_response(res).map_err(Error::Redis))
-            }),
-        )
+        let blocking = msg.blocking_timeout();
+        let req = msg.into_request();
+        let res = match blocking {
+            Some(blocking) => {
+                self.send_blocking(req, blocking, self.response_timeout, false)
+            }
+            None => self.send(req, self.response_timeout, ctx),
+        };
+        let res = res.map(|res| {
+            res.and_then(|res| M::from_response(res.into_resp2()).map_err(Error::Redis))
+        });
+        Cancelable::future(Box::pin(res), blocking.is_some())
+    }
+}
+
+impl Handler<Blocking> for RedisActor {
+    type Result = Cancelable<Self, Result<RespValue, Error>>;
+
+    fn handle(&mut self, msg: Blocking, _ctx: &mut Self::Context) -> Self::Result {
+        let res = self.send_blocking(
+            msg.request,
+            msg.timeout,
+            self.response_timeout,
+            msg.asking,
+        );
+        let res = res.map(|res| res.map(Resp3Value::into_resp2));
+        Cancelable::future(Box::pin(res), true)
+    }
+}
+
+impl Handler<Transaction> for RedisActor {
+    type Result = ResponseFuture<Result<Option<Replies>, Error>>;
+
+    fn handle(&mut self, msg: Transaction, ctx: &mut Self::Context) -> Self::Result {
+        let reqs = msg.into_requests();
+        let replies = self.send_batch(reqs, self.response_timeout, ctx);
+        Box::pin(replies.map(|replies| transaction::decode(replies?)))
+    }
+}
+
+impl Handler<Pipeline> for RedisActor {
+    type Result = ResponseFuture<Result<Replies, Error>>;
+
+    fn handle(&mut self, msg: Pipeline, ctx: &mut Self::Context) -> Self::Result {
+        let reqs = msg.into_requests();
+        let replies = self.send_batch(reqs, self.response_timeout, ctx);
+        Box::pin(replies.map(|replies| {
+            let replies = replies?.into_iter().map(Resp3Value::into_resp2);
+            Ok(Replies::new(replies.collect()))
+        }))
+    }
+}
+
+impl Handler<CheckAndSet> for RedisActor {
+    type Result = ResponseFuture<Result<Option<Replies>, Error>>;
+
+    fn handle(
+        &mut self,
+        mut msg: CheckAndSet,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        if self.shutdown {
+            return Box::pin(future::err(Error::ShuttingDown));
+        }
+        if self.addr.is_empty() {
+            return Box::pin(future::err(Error::NotConnected));
+        }
+        let addr = self.addr.clone();
+        let options = self.options.clone();
+        let timeout = self.response_timeout;
+        Box::pin(async move {
+            transaction::check_and_set(addr, options, timeout, &mut msg, false).await
+        })
+    }
+}
+
+impl Handler<WithTimeout<Command>> for RedisActor {
+    type Result = ResponseFuture<Result<RespValue, Error>>;
+
+    fn handle(
+        &mut self,
+        msg: WithTimeout<Command>,
+        ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let res = self.send(msg.message.0, Some(msg.timeout), ctx);
+        Box::pin(res.map(|res| res.map(Resp3Value::into_resp2)))
+    }
+}
+
+impl<M> Handler<WithTimeout<M>> for RedisActor
+where
+    M: command::Command
+        + Message<Result = Result<<M as command::Command>::Output, Error>>
+        + 'static,
+    <M as command::Command>::Output: Send + 'static,
+{
+    type Result = Cancelable<Self, Result<M::Output, Error>>;
+
+    fn handle(&mut self, msg: WithTimeout<M>, ctx: &mut Self::Context) -> Self::Result {
+        let blocking = msg.message.blocking_timeout();
+        let req = msg.message.into_request();
+        let res = match blocking {
+            Some(blocking) => {
+                self.send_blocking(req, blocking, Some(msg.timeout), false)
+            }
+            None => self.send(req, Some(msg.timeout), ctx),
+        };
+        let res = res.map(|res| {
+            res.and_then(|res| M::from_response(res.into_resp2()).map_err(Error::Redis))
+        });
+        Cancelable::future(Box::pin(res), blocking.is_some())
     }
 }
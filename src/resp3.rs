@@ -0,0 +1,366 @@
+//! RESP3 values and codec
+//!
+//! Requests are still sent as arrays of bulk strings, which RESP3 shares with
+//! RESP2, so only the decoder understands the new types.
+use std::io;
+use std::str;
+
+use bytes::BytesMut;
+use redis_async::resp::{RespCodec, RespValue};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::RespError;
+
+/// Value of the RESP3 protocol
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resp3Value {
+    Nil,
+    SimpleString(String),
+    Error(String),
+    Integer(i64),
+    BulkString(Vec<u8>),
+    Array(Vec<Resp3Value>),
+    Map(Vec<(Resp3Value, Resp3Value)>),
+    Set(Vec<Resp3Value>),
+    Double(f64),
+    Boolean(bool),
+    /// Decimal representation of an integer of arbitrary size
+    BigNumber(String),
+    /// Text with its format, e.g. `txt` or `mkd`
+    Verbatim(String, Vec<u8>),
+    /// Out-of-band data, never a reply to a request
+    Push(Vec<Resp3Value>),
+}
+
+impl Resp3Value {
+    /// Convert into the closest RESP2 value.
+    ///
+    /// Maps are flattened into arrays of keys and values, sets become arrays,
+    /// booleans become integers and the other new types become bulk strings,
+    /// like the replies of the server speaking RESP2.
+    pub fn into_resp2(self) -> RespValue {
+        match self {
+            Resp3Value::Nil => RespValue::Nil,
+            Resp3Value::SimpleString(s) => RespValue::SimpleString(s),
+            Resp3Value::Error(e) => RespValue::Error(e),
+            Resp3Value::Integer(i) => RespValue::Integer(i),
+            Resp3Value::BulkString(b) => RespValue::BulkString(b),
+            Resp3Value::Array(a) | Resp3Value::Set(a) | Resp3Value::Push(a) => {
+                RespValue::Array(a.into_iter().map(Resp3Value::into_resp2).collect())
+            }
+            Resp3Value::Map(m) => RespValue::Array(
+                m.into_iter()
+                    .flat_map(|(k, v)| vec![k.into_resp2(), v.into_resp2()])
+                    .collect(),
+            ),
+            Resp3Value::Double(d) => RespValue::BulkString(d.to_string().into_bytes()),
+            Resp3Value::Boolean(b) => RespValue::Integer(b as i64),
+            Resp3Value::BigNumber(n) => RespValue::BulkString(n.into_bytes()),
+            Resp3Value::Verbatim(_, text) => RespValue::BulkString(text),
+        }
+    }
+}
+
+impl From<RespValue> for Resp3Value {
+    fn from(value: RespValue) -> Self {
+        match value {
+            RespValue::Nil => Resp3Value::Nil,
+            RespValue::SimpleString(s) => Resp3Value::SimpleString(s),
+            RespValue::Error(e) => Resp3Value::Error(e),
+            RespValue::Integer(i) => Resp3Value::Integer(i),
+            RespValue::BulkString(b) => Resp3Value::BulkString(b),
+            RespValue::Array(a) => {
+                Resp3Value::Array(a.into_iter().map(Resp3Value::from).collect())
+            }
+        }
+    }
+}
+
+/// Codec sending RESP2 requests and decoding RESP3 (and RESP2) replies.
+///
+/// Attributes are skipped, the value following them is returned.
+#[derive(Debug, Default)]
+pub struct Resp3Codec;
+
+impl Encoder for Resp3Codec {
+    type Item = RespValue;
+    type Error = io::Error;
+
+    fn encode(&mut self, msg: RespValue, buf: &mut BytesMut) -> Result<(), Self::Error> {
+        RespCodec.encode(msg, buf)
+    }
+}
+
+impl Decoder for Resp3Codec {
+    type Item = Resp3Value;
+    type Error = RespError;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Resp3Value>, Self::Error> {
+        match parse(buf, 0)? {
+            Some((value, pos)) => {
+                let _ = buf.split_to(pos);
+                Ok(Some(value))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+fn invalid(msg: &str) -> RespError {
+    RespError::RESP(msg.into(), None)
+}
+
+/// Read a line starting at `pos`, returning it without `\r\n` and the position after
+fn line(buf: &[u8], pos: usize) -> Result<Option<(&str, usize)>, RespError> {
+    match buf[pos..].windows(2).position(|w| w == b"\r\n") {
+        Some(len) => {
+            let line = str::from_utf8(&buf[pos..pos + len])
+                .map_err(|_| invalid("invalid utf-8 in RESP3 line"))?;
+            Ok(Some((line, pos + len + 2)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Read `len` bytes followed by `\r\n` starting at `pos`
+fn blob(
+    buf: &[u8],
+    pos: usize,
+    len: usize,
+) -> Result<Option<(Vec<u8>, usize)>, RespError> {
+    if buf.len() < pos + len + 2 {
+        return Ok(None);
+    }
+    if &buf[pos + len..pos + len + 2] != b"\r\n" {
+        return Err(invalid("missing terminator of RESP3 blob"));
+    }
+    Ok(Some((buf[pos..pos + len].to_vec(), pos + len + 2)))
+}
+
+fn parse_int(s: &str) -> Result<i64, RespError> {
+    s.parse().map_err(|_| invalid("invalid RESP3 integer"))
+}
+
+fn parse_len(s: &str) -> Result<Option<usize>, RespError> {
+    match parse_int(s)? {
+        -1 => Ok(None),
+        n if n >= 0 => Ok(Some(n as usize)),
+        _ => Err(invalid("invalid RESP3 length")),
+    }
+}
+
+/// Parse `n` values starting at `pos`
+fn parse_n(
+    buf: &[u8],
+    mut pos: usize,
+    n: usize,
+) -> Result<Option<(Vec<Resp3Value>, usize)>, RespError> {
+    // `n` is told by the server, a value takes at least a byte of `buf`
+    let mut values = Vec::with_capacity(n.min(buf.len().saturating_sub(pos)));
+    for _ in 0..n {
+        match parse(buf, pos)? {
+            Some((value, next)) => {
+                values.push(value);
+                pos = next;
+            }
+            None => return Ok(None),
+        }
+    }
+    Ok(Some((values, pos)))
+}
+
+/// Parse a value starting at `pos`. Returns `None` if `buf` holds a partial value.
+fn parse(buf: &[u8], pos: usize) -> Result<Option<(Resp3Value, usize)>, RespError> {
+    if buf.len() <= pos {
+        return Ok(None);
+    }
+    let kind = buf[pos];
+    let (header, pos) = match line(buf, pos + 1)? {
+        Some(line) => line,
+        None => return Ok(None),
+    };
+
+    let parsed = match kind {
+        b'+' => Some((Resp3Value::SimpleString(header.to_string()), pos)),
+        b'-' => Some((Resp3Value::Error(header.to_string()), pos)),
+        b':' => Some((Resp3Value::Integer(parse_int(header)?), pos)),
+        b'_' => Some((Resp3Value::Nil, pos)),
+        b'#' => match header {
+            "t" => Some((Resp3Value::Boolean(true), pos)),
+            "f" => Some((Resp3Value::Boolean(false), pos)),
+            _ => return Err(invalid("invalid RESP3 boolean")),
+        },
+        b',' => {
+            // `inf` and `-inf` are understood by `f64::from_str` as they are
+            let d = if header == "nan" { "NaN" } else { header };
+            let d = d.parse().map_err(|_| invalid("invalid RESP3 double"))?;
+            Some((Resp3Value::Double(d), pos))
+        }
+        b'(' => Some((Resp3Value::BigNumber(header.to_string()), pos)),
+        b'$' => match parse_len(header)? {
+            None => Some((Resp3Value::Nil, pos)),
+            Some(len) => {
+                blob(buf, pos, len)?.map(|(b, pos)| (Resp3Value::BulkString(b), pos))
+            }
+        },
+        b'!' => match parse_len(header)? {
+            None => return Err(invalid("invalid RESP3 blob error")),
+            Some(len) => blob(buf, pos, len)?.map(|(b, pos)| {
+                (
+                    Resp3Value::Error(String::from_utf8_lossy(&b).into_owned()),
+                    pos,
+                )
+            }),
+        },
+        b'=' => match parse_len(header)? {
+            Some(len) if len >= 4 => blob(buf, pos, len)?.map(|(mut b, pos)| {
+                let text = b.split_off(4);
+                let format = String::from_utf8_lossy(&b[..3]).into_owned();
+                (Resp3Value::Verbatim(format, text), pos)
+            }),
+            _ => return Err(invalid("invalid RESP3 verbatim string")),
+        },
+        b'*' => match parse_len(header)? {
+            None => Some((Resp3Value::Nil, pos)),
+            Some(n) => parse_n(buf, pos, n)?.map(|(a, pos)| (Resp3Value::Array(a), pos)),
+        },
+        b'~' => match parse_len(header)? {
+            None => return Err(invalid("invalid RESP3 set")),
+            Some(n) => parse_n(buf, pos, n)?.map(|(a, pos)| (Resp3Value::Set(a), pos)),
+        },
+        b'>' => match parse_len(header)? {
+            None => return Err(invalid("invalid RESP3 push")),
+            Some(n) => parse_n(buf, pos, n)?.map(|(a, pos)| (Resp3Value::Push(a), pos)),
+        },
+        b'%' | b'|' => {
+            let n = match parse_len(header)?.and_then(|n| n.checked_mul(2)) {
+                None => return Err(invalid("invalid RESP3 map")),
+                Some(n) => n,
+            };
+            let (values, pos) = match parse_n(buf, pos, n)? {
+                Some(parsed) => parsed,
+                None => return Ok(None),
+            };
+            if kind == b'|' {
+                // attributes precede the value they describe
+                return parse(buf, pos);
+            }
+            let mut values = values.into_iter();
+            let mut map = Vec::with_capacity(n / 2);
+            while let (Some(k), Some(v)) = (values.next(), values.next()) {
+                map.push((k, v));
+            }
+            Some((Resp3Value::Map(map), pos))
+        }
+        _ => return Err(invalid("unknown RESP3 type")),
+    };
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+    use tokio_util::codec::Decoder;
+
+    use super::{Resp3Codec, Resp3Value};
+
+    fn decode(input: &[u8]) -> Option<Resp3Value> {
+        let mut buf = BytesMut::from(input);
+        let value = Resp3Codec.decode(&mut buf).unwrap();
+        if value.is_some() {
+            assert!(buf.is_empty());
+        }
+        value
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(b"_\r\n"), Some(Resp3Value::Nil));
+        assert_eq!(decode(b"#t\r\n"), Some(Resp3Value::Boolean(true)));
+        assert_eq!(decode(b",1.5\r\n"), Some(Resp3Value::Double(1.5)));
+        match decode(b",-inf\r\n") {
+            Some(Resp3Value::Double(d)) if d.is_infinite() && d < 0.0 => (),
+            res => panic!("Should not happen {:?}", res),
+        }
+        match decode(b",nan\r\n") {
+            Some(Resp3Value::Double(d)) if d.is_nan() => (),
+            res => panic!("Should not happen {:?}", res),
+        }
+        assert_eq!(
+            decode(b"(3492890328409238509324850943850943825024385\r\n"),
+            Some(Resp3Value::BigNumber(
+                "3492890328409238509324850943850943825024385".into()
+            ))
+        );
+        assert_eq!(
+            decode(b"=15\r\ntxt:Some string\r\n"),
+            Some(Resp3Value::Verbatim("txt".into(), b"Some string".to_vec()))
+        );
+        assert_eq!(
+            decode(b"!21\r\nSYNTAX invalid syntax\r\n"),
+            Some(Resp3Value::Error("SYNTAX invalid syntax".into()))
+        );
+        assert_eq!(
+            decode(b"%2\r\n+first\r\n:1\r\n+second\r\n:2\r\n"),
+            Some(Resp3Value::Map(vec![
+                (
+                    Resp3Value::SimpleString("first".into()),
+                    Resp3Value::Integer(1)
+                ),
+                (
+                    Resp3Value::SimpleString("second".into()),
+                    Resp3Value::Integer(2)
+                ),
+            ]))
+        );
+        assert_eq!(
+            decode(b"~2\r\n$3\r\nfoo\r\n$-1\r\n"),
+            Some(Resp3Value::Set(vec![
+                Resp3Value::BulkString(b"foo".to_vec()),
+                Resp3Value::Nil,
+            ]))
+        );
+        assert_eq!(
+            decode(b"|1\r\n+ttl\r\n:3600\r\n:42\r\n"),
+            Some(Resp3Value::Integer(42))
+        );
+        assert_eq!(
+            decode(b">2\r\n+invalidate\r\n*1\r\n$3\r\nfoo\r\n"),
+            Some(Resp3Value::Push(vec![
+                Resp3Value::SimpleString("invalidate".into()),
+                Resp3Value::Array(vec![Resp3Value::BulkString(b"foo".to_vec())]),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_decode_partial() {
+        assert_eq!(decode(b""), None);
+        assert_eq!(decode(b"+OK\r"), None);
+        assert_eq!(decode(b"$3\r\nfo"), None);
+        assert_eq!(decode(b"%1\r\n+first\r\n"), None);
+        assert_eq!(decode(b"|1\r\n+ttl\r\n:3600\r\n"), None);
+    }
+
+    #[test]
+    fn test_decode_huge_length() {
+        assert_eq!(decode(b"*9223372036854775807\r\n+OK\r\n"), None);
+        assert_eq!(decode(b"~9223372036854775807\r\n"), None);
+        assert_eq!(decode(b"%9223372036854775807\r\n+first\r\n"), None);
+    }
+
+    #[test]
+    fn test_into_resp2() {
+        let value = Resp3Value::Map(vec![(
+            Resp3Value::SimpleString("a".into()),
+            Resp3Value::Boolean(true),
+        )]);
+        assert_eq!(
+            value.into_resp2(),
+            resp_array![
+                redis_async::resp::RespValue::SimpleString("a".into()),
+                redis_async::resp::RespValue::Integer(1)
+            ]
+        );
+    }
+}
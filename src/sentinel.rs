@@ -0,0 +1,144 @@
+//! Redis Sentinel
+//!
+//! Sentinels monitor a master and promote one of its replicas when it fails.
+//! `RedisActor` started with `RedisActorBuilder::start_sentinel` asks them for
+//! the address of the master every time it connects, and drops its connection
+//! when a sentinel announces a failover with `+switch-master`.
+use actix::prelude::*;
+use actix::WeakAddr;
+use futures::StreamExt;
+
+use crate::command::SentinelGetMasterAddrByName;
+use crate::listener::Listener;
+use crate::pubsub::{PubSubMessage, RedisSubscriber, Subscribe};
+use crate::redis::{self, ConnectOptions, RedisActor};
+use crate::Error;
+
+/// Channel the sentinels announce failovers on
+const SWITCH_MASTER: &str = "+switch-master";
+
+/// Sentinels monitoring a master
+#[derive(Debug, Clone)]
+pub struct Sentinel {
+    master_name: String,
+    sentinels: Vec<String>,
+    options: ConnectOptions,
+}
+
+impl Sentinel {
+    /// Monitor the master named `master_name` with `sentinels`, given as
+    /// `host:port` addresses. They are asked in order until one of them knows
+    /// the master.
+    pub fn new<S, I>(master_name: S, sentinels: I) -> Self
+    where
+        S: Into<String>,
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        Sentinel {
+            master_name: master_name.into(),
+            sentinels: sentinels.into_iter().map(Into::into).collect(),
+            options: ConnectOptions::default(),
+        }
+    }
+
+    /// Set the connection options of the sentinels, which may not share the
+    /// password of the master
+    pub fn options(mut self, options: ConnectOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn master_name(&self) -> &str {
+        &self.master_name
+    }
+
+    /// Ask the sentinels for the address of the master, returning the index of
+    /// the sentinel which replied along with it
+    pub(crate) async fn master_addr(&self) -> Result<(usize, String), Error> {
+        let mut last_error = Error::Sentinel(format!(
+            "no sentinel is configured for {}",
+            self.master_name
+        ));
+        for (index, sentinel) in self.sentinels.iter().enumerate() {
+            let cmd = SentinelGetMasterAddrByName {
+                master_name: self.master_name.clone(),
+            };
+            match redis::command(sentinel.clone(), self.options.clone(), cmd).await {
+                Ok(Some((host, port))) => {
+                    return Ok((index, format!("{}:{}", host, port)))
+                }
+                Ok(None) => {
+                    warn!("Sentinel {} does not know {}", sentinel, self.master_name);
+                    last_error =
+                        Error::Sentinel(format!("unknown master {}", self.master_name));
+                }
+                Err(e) => {
+                    warn!("Can not query sentinel {}: {}", sentinel, e);
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Ask the sentinel at `index` first from now on
+    pub(crate) fn prefer(&mut self, index: usize) {
+        if index < self.sentinels.len() {
+            let sentinel = self.sentinels.remove(index);
+            self.sentinels.insert(0, sentinel);
+        }
+    }
+
+    /// Subscribe to the failovers announced by the preferred sentinel, or
+    /// the next one once it can not be reached, sending `SwitchMaster` to
+    /// `addr` on failovers of the master
+    pub(crate) fn watch(&self, addr: WeakAddr<RedisActor>) -> Addr<RedisSubscriber> {
+        let subscriber =
+            RedisSubscriber::start_any(self.sentinels.clone(), self.options.clone());
+        let (listener, mut messages) = Listener::stream();
+        subscriber.do_send(Subscribe {
+            channels: vec![SWITCH_MASTER.to_string()],
+            listener,
+        });
+
+        let master_name = self.master_name.clone();
+        // ends once the subscriber, owned by `addr`, stops
+        actix_rt::spawn(async move {
+            while let Some(msg) = messages.next().await {
+                let master = match switched_master(&msg, &master_name) {
+                    Some(master) => master,
+                    None => continue,
+                };
+                match addr.upgrade() {
+                    Some(addr) => addr.do_send(SwitchMaster { addr: master }),
+                    None => break,
+                }
+            }
+        });
+        subscriber
+    }
+}
+
+/// Parse `<master name> <old ip> <old port> <new ip> <new port>` announced
+/// on `+switch-master`, returning the new address of `master_name`
+fn switched_master(msg: &PubSubMessage, master_name: &str) -> Option<String> {
+    let payload = String::from_utf8_lossy(&msg.payload);
+    let parts: Vec<_> = payload.split_whitespace().collect();
+    match parts.as_slice() {
+        [name, _, _, host, port] if *name == master_name => {
+            Some(format!("{}:{}", host, port))
+        }
+        _ => None,
+    }
+}
+
+/// The sentinels promoted `addr` to master
+#[doc(hidden)]
+pub struct SwitchMaster {
+    pub addr: String,
+}
+
+impl Message for SwitchMaster {
+    type Result = ();
+}
@@ -15,7 +15,8 @@ use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
 use time::{self, Duration};
 
 use crate::command::{self, Del, Expiration, Get, Set};
-use crate::redis::RedisActor;
+use crate::redis::{ConnectOptions, RedisActor};
+use crate::sentinel::Sentinel;
 use crate::RedisClusterActor;
 
 /// Use redis as session storage.
@@ -37,6 +38,27 @@ impl RedisSession {
         Self::from_redis(redis_addr, key)
     }
 
+    /// Create new redis session backend
+    ///
+    /// * `server` - redis server
+    /// * `options` - credentials and database used for the connection
+    pub fn new_with<S: Into<String>>(
+        server: S,
+        options: ConnectOptions,
+        key: &[u8],
+    ) -> RedisSession {
+        let redis_addr = RedisActor::start_with(server, options);
+        Self::from_redis(redis_addr, key)
+    }
+
+    /// Create new redis session backend with the master monitored by Redis Sentinel
+    ///
+    /// * `sentinel` - the sentinels and the name of the master
+    pub fn new_sentinel(sentinel: Sentinel, key: &[u8]) -> RedisSession {
+        let redis_addr = RedisActor::start_sentinel(sentinel);
+        Self::from_redis(redis_addr, key)
+    }
+
     /// Create new redis session backend
     ///
     /// * `addr` - Addr of the redis actor
@@ -96,7 +96,7 @@ pub fn hash_slot(key: &[u8]) -> u16 {
     crc & 0x3FFF
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HashError {
     expected: u16,
     actual: u16,
@@ -115,13 +115,13 @@ impl std::fmt::Display for HashError {
 
 impl std::error::Error for HashError {}
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 enum HasherState {
     Init,
     Valid(u16),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Hasher {
     state: HasherState,
 }
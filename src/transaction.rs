@@ -0,0 +1,362 @@
+//! MULTI/EXEC transactions
+use std::fmt;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use actix::Message;
+use futures::future::LocalBoxFuture;
+use futures::{stream, SinkExt, StreamExt};
+use redis_async::resp::RespValue;
+use tokio_util::codec::Framed;
+
+use crate::command::{self, Asking, Command};
+use crate::redis::{self, ConnectOptions};
+use crate::resp3::{Resp3Codec, Resp3Value};
+use crate::slot::{HashError, Hasher};
+use crate::transport::Stream;
+use crate::{Error, RespError};
+
+const DEFAULT_MAX_RETRIES: usize = 10;
+
+/// Commands executed atomically with `MULTI` and `EXEC`
+///
+/// The commands are written to the connection at once, so commands of other
+/// actors can not be interleaved.
+#[derive(Debug, Clone, Default)]
+pub struct Transaction {
+    requests: Vec<RespValue>,
+    hasher: Hasher,
+    hash_error: Option<HashError>,
+}
+
+impl Message for Transaction {
+    /// Replies of the commands, `None` if the transaction was aborted because
+    /// a watched key changed
+    type Result = Result<Option<Replies>, Error>;
+}
+
+impl Transaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue `cmd`, returning the handle to get its reply from `Replies`
+    pub fn add<C: Command>(&mut self, cmd: C) -> Handle<C> {
+        if self.hash_error.is_none() {
+            if let Err(e) = cmd.hash_keys(&mut self.hasher) {
+                self.hash_error = Some(e);
+            }
+        }
+
+        self.requests.push(cmd.into_request());
+        Handle::new(self.requests.len() - 1)
+    }
+
+    /// Number of the queued commands
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// Calculate the slot number of the keys of all commands.
+    ///
+    /// # Failures
+    /// If the keys falls into different slots, an error is reported
+    pub fn key_slot(&self) -> Result<Option<u16>, HashError> {
+        match self.hash_error {
+            Some(ref e) => Err(e.clone()),
+            None => Ok(self.hasher.get()),
+        }
+    }
+
+    /// Requests to write, wrapped in `MULTI` and `EXEC`
+    pub(crate) fn into_requests(self) -> Vec<RespValue> {
+        let mut requests = Vec::with_capacity(self.requests.len() + 2);
+        requests.push(command::Multi.into_request());
+        requests.extend(self.requests);
+        requests.push(command::Exec.into_request());
+        requests
+    }
+}
+
+/// Reference to a command queued in `Transaction` or `Pipeline`
+#[derive(Debug)]
+pub struct Handle<C> {
+    index: usize,
+    _command: PhantomData<fn() -> C>,
+}
+
+impl<C> Handle<C> {
+    pub(crate) fn new(index: usize) -> Self {
+        Handle {
+            index,
+            _command: PhantomData,
+        }
+    }
+}
+
+impl<C> Clone for Handle<C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C> Copy for Handle<C> {}
+
+/// Replies of the commands of an executed `Transaction` or `Pipeline`
+#[derive(Debug)]
+pub struct Replies(Vec<RespValue>);
+
+impl Replies {
+    pub(crate) fn new(replies: Vec<RespValue>) -> Self {
+        Replies(replies)
+    }
+
+    /// Parse the reply of the command referenced by `handle`
+    pub fn get<C: Command>(&self, handle: &Handle<C>) -> Result<C::Output, Error> {
+        match self.0.get(handle.index) {
+            Some(res) => C::from_response(res.clone()).map_err(Error::Redis),
+            None => Err(Error::Redis(RespError::RESP(
+                "no reply for the command".into(),
+                None,
+            ))),
+        }
+    }
+
+    /// Raw replies of the commands, in the order they were added
+    pub fn into_inner(self) -> Vec<RespValue> {
+        self.0
+    }
+}
+
+/// Closure building the transaction of `CheckAndSet`
+pub type BuildTransaction = Box<
+    dyn for<'a> FnMut(&'a mut Watcher) -> LocalBoxFuture<'a, Result<Transaction, Error>>
+        + Send,
+>;
+
+/// Optimistic locking with `WATCH`.
+///
+/// A new connection is opened for the keys to be watched without commands of
+/// other actors in between. The closure reads the current values through the
+/// `Watcher` and returns the transaction to execute. If a watched key was
+/// modified meanwhile, the keys are watched again and the closure is called
+/// once more, up to `max_retries` times.
+///
+/// ```rust,ignore
+/// let cas = CheckAndSet::new(vec!["counter".into()], |watcher| {
+///     async move {
+///         let value = watcher.send(Get { key: "counter".into() }).await?;
+///         let mut tx = Transaction::new();
+///         tx.add(Set::new("counter", next(value)));
+///         Ok(tx)
+///     }
+///     .boxed_local()
+/// });
+/// let replies = addr.send(cas).await?;
+/// ```
+pub struct CheckAndSet {
+    keys: Vec<String>,
+    max_retries: usize,
+    build: BuildTransaction,
+}
+
+impl Message for CheckAndSet {
+    /// Replies of the commands, `None` if the watched keys kept changing
+    /// after `max_retries` retries
+    type Result = Result<Option<Replies>, Error>;
+}
+
+impl CheckAndSet {
+    pub fn new<F>(keys: Vec<String>, build: F) -> Self
+    where
+        F: for<'a> FnMut(
+                &'a mut Watcher,
+            ) -> LocalBoxFuture<'a, Result<Transaction, Error>>
+            + Send
+            + 'static,
+    {
+        CheckAndSet {
+            keys,
+            max_retries: DEFAULT_MAX_RETRIES,
+            build: Box::new(build),
+        }
+    }
+
+    /// Set how many times the transaction is retried when a watched key was
+    /// modified. Default is 10.
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Calculate the slot number of the watched keys.
+    ///
+    /// # Failures
+    /// If the keys falls into different slots, an error is reported
+    pub fn key_slot(&self) -> Result<Option<u16>, HashError> {
+        let mut hasher = Hasher::new();
+        for key in self.keys.iter() {
+            hasher.hash_str(key)?;
+        }
+        Ok(hasher.get())
+    }
+}
+
+impl fmt::Debug for CheckAndSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CheckAndSet")
+            .field("keys", &self.keys)
+            .field("max_retries", &self.max_retries)
+            .finish()
+    }
+}
+
+/// Connection dedicated to a `CheckAndSet`
+pub struct Watcher {
+    framed: Framed<Stream, Resp3Codec>,
+    timeout: Option<Duration>,
+    asking: bool,
+}
+
+impl Watcher {
+    /// Send `cmd` and wait for its reply
+    pub async fn send<C: Command>(&mut self, cmd: C) -> Result<C::Output, Error> {
+        let mut replies = self.exchange(vec![cmd.into_request()]).await?;
+        let res = replies.pop().ok_or(Error::Disconnected)?;
+        C::from_response(res.into_resp2()).map_err(Error::Redis)
+    }
+
+    async fn exec(
+        &mut self,
+        transaction: Transaction,
+    ) -> Result<Option<Replies>, Error> {
+        let replies = self.exchange(transaction.into_requests()).await?;
+        decode(replies)
+    }
+
+    /// Send `requests` at once and wait for their replies within the response
+    /// timeout. Each request is preceded by `ASKING` when the slot is being
+    /// imported by the node. Cluster redirections fail the exchange.
+    async fn exchange(
+        &mut self,
+        requests: Vec<RespValue>,
+    ) -> Result<Vec<Resp3Value>, Error> {
+        let asking = self.asking;
+        let framed = &mut self.framed;
+        let exchange = async move {
+            let len = requests.len();
+            let requests = requests.into_iter().flat_map(|req| {
+                let asking = if asking {
+                    Some(Asking.into_request())
+                } else {
+                    None
+                };
+                asking.into_iter().chain(Some(req))
+            });
+            framed.send_all(&mut stream::iter(requests.map(Ok))).await?;
+
+            let mut replies = Vec::with_capacity(len);
+            while replies.len() < len {
+                if asking {
+                    let asked = next(framed).await?.into_resp2();
+                    Asking::from_response(asked).map_err(Error::Redis)?;
+                }
+                replies.push(next(framed).await?);
+            }
+            Ok(replies)
+        };
+        let replies = match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, exchange)
+                .await
+                .unwrap_or(Err(Error::Timeout))?,
+            None => exchange.await?,
+        };
+
+        let redirection = replies.iter().find_map(|res| match res {
+            Resp3Value::Error(e) if e.starts_with("MOVED ") || e.starts_with("ASK ") => {
+                Some(e.clone())
+            }
+            _ => None,
+        });
+        match redirection {
+            Some(e) => Err(Error::Redis(RespError::Remote(e))),
+            None => Ok(replies),
+        }
+    }
+}
+
+async fn next(framed: &mut Framed<Stream, Resp3Codec>) -> Result<Resp3Value, Error> {
+    match framed.next().await {
+        Some(Ok(res)) => Ok(res),
+        Some(Err(e)) => Err(e.into()),
+        None => Err(Error::Disconnected),
+    }
+}
+
+/// Run `cas` over a new connection to `addr`, failing the exchanges with the
+/// server which take longer than `timeout`. `asking` follows an ASK
+/// redirection of a cluster node.
+pub(crate) async fn check_and_set(
+    addr: String,
+    options: ConnectOptions,
+    timeout: Option<Duration>,
+    cas: &mut CheckAndSet,
+    asking: bool,
+) -> Result<Option<Replies>, Error> {
+    let stream = redis::connect(addr, options).await?;
+    let mut watcher = Watcher {
+        framed: Framed::new(stream, Resp3Codec),
+        timeout,
+        asking,
+    };
+
+    let mut retry = 0;
+    loop {
+        let watch = command::Watch {
+            keys: cas.keys.clone(),
+        };
+        watcher.send(watch).await?;
+
+        let transaction = (cas.build)(&mut watcher).await?;
+        if let Some(replies) = watcher.exec(transaction).await? {
+            return Ok(Some(replies));
+        }
+
+        if retry >= cas.max_retries {
+            return Ok(None);
+        }
+        retry += 1;
+        info!("Watched keys were modified: retry = {}", retry);
+    }
+}
+
+/// Decode the replies of `MULTI`, the queued commands and `EXEC`
+pub(crate) fn decode(replies: Vec<Resp3Value>) -> Result<Option<Replies>, Error> {
+    let mut replies: Vec<RespValue> =
+        replies.into_iter().map(Resp3Value::into_resp2).collect();
+
+    match replies.pop() {
+        Some(RespValue::Array(replies)) => Ok(Some(Replies(replies))),
+        Some(RespValue::Nil) => Ok(None),
+        // EXECABORT, report why the command was not queued
+        Some(RespValue::Error(e)) => {
+            let e = replies
+                .into_iter()
+                .filter_map(|res| match res {
+                    RespValue::Error(e) => Some(e),
+                    _ => None,
+                })
+                .next()
+                .unwrap_or(e);
+            Err(Error::Redis(RespError::Remote(e)))
+        }
+        res => Err(Error::Redis(RespError::RESP(
+            "invalid response for EXEC".into(),
+            res,
+        ))),
+    }
+}
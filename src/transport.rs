@@ -0,0 +1,314 @@
+//! Transports used to reach a redis server
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use actix::actors::resolver::{Connect, Resolver};
+use actix::prelude::*;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+#[cfg(unix)]
+use tokio::net::UnixStream;
+
+use crate::redis::ConnectOptions;
+use crate::Error;
+
+#[cfg(feature = "tls")]
+pub use self::tls::TlsConfig;
+
+/// Address of a redis server
+///
+/// * `host:port` or `redis://host:port` - plain TCP
+/// * `rediss://host:port` - TLS over TCP
+/// * `unix:///path/to/redis.sock` - unix domain socket
+#[derive(Debug, PartialEq)]
+enum Address<'a> {
+    Tcp(&'a str),
+    Tls(&'a str),
+    Unix(&'a str),
+}
+
+impl<'a> Address<'a> {
+    fn parse(addr: &'a str) -> Self {
+        let mut parts = addr.splitn(2, "://");
+        match (parts.next(), parts.next()) {
+            (Some("rediss"), Some(addr)) => Address::Tls(addr),
+            (Some("redis"), Some(addr)) => Address::Tcp(addr),
+            (Some("unix"), Some(path)) => Address::Unix(path),
+            _ => Address::Tcp(addr),
+        }
+    }
+}
+
+/// Connection to a redis server
+pub(crate) enum Stream {
+    Tcp(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<tokio_rustls::client::TlsStream<TcpStream>>),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+/// Open a connection to `addr`
+pub(crate) async fn connect(
+    addr: &str,
+    options: &ConnectOptions,
+) -> Result<Stream, Error> {
+    match Address::parse(addr) {
+        #[cfg(feature = "tls")]
+        Address::Tcp(addr) => match options.tls {
+            Some(ref tls) => tls::connect(tcp(addr, options).await?, addr, tls).await,
+            None => Ok(Stream::Tcp(tcp(addr, options).await?)),
+        },
+        #[cfg(not(feature = "tls"))]
+        Address::Tcp(addr) => Ok(Stream::Tcp(tcp(addr, options).await?)),
+        #[cfg(feature = "tls")]
+        Address::Tls(addr) => match options.tls {
+            Some(ref tls) => tls::connect(tcp(addr, options).await?, addr, tls).await,
+            None => {
+                let tls = TlsConfig::default();
+                tls::connect(tcp(addr, options).await?, addr, &tls).await
+            }
+        },
+        #[cfg(not(feature = "tls"))]
+        Address::Tls(_) => Err(Error::IoError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "connecting over TLS requires the `tls` feature",
+        ))),
+        #[cfg(unix)]
+        Address::Unix(path) => Ok(Stream::Unix(UnixStream::connect(path).await?)),
+        #[cfg(not(unix))]
+        Address::Unix(_) => Err(Error::IoError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "unix domain sockets are not supported on this platform",
+        ))),
+    }
+}
+
+async fn tcp(addr: &str, options: &ConnectOptions) -> Result<TcpStream, Error> {
+    let stream = Resolver::from_registry()
+        .send(Connect::host(addr))
+        .await
+        .map_err(|_| Error::Disconnected)??;
+    stream.set_nodelay(options.nodelay)?;
+    if options.keepalive.is_some() {
+        stream.set_keepalive(options.keepalive)?;
+    }
+    Ok(stream)
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_read(cx, buf),
+            #[cfg(feature = "tls")]
+            Stream::Tls(s) => Pin::new(s).poll_read(cx, buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_write(cx, buf),
+            #[cfg(feature = "tls")]
+            Stream::Tls(s) => Pin::new(s).poll_write(cx, buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_flush(cx),
+            #[cfg(feature = "tls")]
+            Stream::Tls(s) => Pin::new(s).poll_flush(cx),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_shutdown(cx),
+            #[cfg(feature = "tls")]
+            Stream::Tls(s) => Pin::new(s).poll_shutdown(cx),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_shutdown(cx),
+        }
+    }
+}
+
+#[cfg(feature = "tls")]
+mod tls {
+    use std::fmt;
+    use std::io;
+    use std::net::IpAddr;
+    use std::sync::Arc;
+
+    use tokio::net::TcpStream;
+    use tokio_rustls::rustls::internal::pemfile;
+    use tokio_rustls::rustls::ClientConfig;
+    use tokio_rustls::webpki::DNSNameRef;
+    use tokio_rustls::TlsConnector;
+
+    use super::Stream;
+    use crate::Error;
+
+    /// TLS settings of the connections to redis servers
+    ///
+    /// Certificates are verified against the host part of the server address,
+    /// which has to be a DNS name. Connections to IP addresses, such as the
+    /// cluster nodes reported by `CLUSTER SLOTS`, fail unless `server_name` is
+    /// set to the name the certificates are issued for.
+    #[derive(Clone)]
+    pub struct TlsConfig {
+        config: Arc<ClientConfig>,
+        server_name: Option<String>,
+    }
+
+    impl TlsConfig {
+        /// Create TLS settings trusting the Mozilla root certificates
+        pub fn new() -> Self {
+            let mut config = ClientConfig::new();
+            config
+                .root_store
+                .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+            Self::from_config(config)
+        }
+
+        /// Create TLS settings from a rustls client configuration
+        pub fn from_config(config: ClientConfig) -> Self {
+            TlsConfig {
+                config: Arc::new(config),
+                server_name: None,
+            }
+        }
+
+        /// Trust the PEM encoded CA certificates in addition to the current roots
+        pub fn root_certificates(mut self, pem: &[u8]) -> io::Result<Self> {
+            let config = Arc::make_mut(&mut self.config);
+            match config.root_store.add_pem_file(&mut &pem[..]) {
+                Ok((valid, _)) if valid > 0 => Ok(self),
+                _ => Err(invalid_data("no valid CA certificate found")),
+            }
+        }
+
+        /// Authenticate with the PEM encoded certificate chain and private key
+        pub fn client_certificate(
+            mut self,
+            certs: &[u8],
+            key: &[u8],
+        ) -> io::Result<Self> {
+            let certs = pemfile::certs(&mut &certs[..])
+                .map_err(|_| invalid_data("invalid client certificate"))?;
+            let mut keys = pemfile::pkcs8_private_keys(&mut &key[..])
+                .map_err(|_| invalid_data("invalid private key"))?;
+            if keys.is_empty() {
+                keys = pemfile::rsa_private_keys(&mut &key[..])
+                    .map_err(|_| invalid_data("invalid private key"))?;
+            }
+            let key = keys
+                .into_iter()
+                .next()
+                .ok_or_else(|| invalid_data("no private key found"))?;
+
+            Arc::make_mut(&mut self.config)
+                .set_single_client_cert(certs, key)
+                .map_err(|e| invalid_data(&e.to_string()))?;
+            Ok(self)
+        }
+
+        /// Use `name` for SNI and certificate verification instead of the host
+        /// part of the server address
+        pub fn server_name<S: Into<String>>(mut self, name: S) -> Self {
+            self.server_name = Some(name.into());
+            self
+        }
+    }
+
+    impl Default for TlsConfig {
+        fn default() -> Self {
+            TlsConfig::new()
+        }
+    }
+
+    impl fmt::Debug for TlsConfig {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_struct("TlsConfig")
+                .field("server_name", &self.server_name)
+                .finish()
+        }
+    }
+
+    fn invalid_data(msg: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+    }
+
+    pub(super) async fn connect(
+        stream: TcpStream,
+        addr: &str,
+        config: &TlsConfig,
+    ) -> Result<Stream, Error> {
+        let name = match config.server_name {
+            Some(ref name) => name.as_str(),
+            None => {
+                // strip the port number
+                let host = addr.rsplitn(2, ':').last().unwrap_or(addr);
+                let host = host.trim_start_matches('[').trim_end_matches(']');
+                if host.parse::<IpAddr>().is_ok() {
+                    return Err(Error::IoError(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("TlsConfig::server_name is required to verify {}", host),
+                    )));
+                }
+                host
+            }
+        };
+        let domain = DNSNameRef::try_from_ascii_str(name)
+            .map_err(|_| invalid_data("invalid server name"))?;
+
+        let stream = TlsConnector::from(config.config.clone())
+            .connect(domain, stream)
+            .await?;
+        Ok(Stream::Tls(Box::new(stream)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Address;
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(
+            Address::parse("127.0.0.1:6379"),
+            Address::Tcp("127.0.0.1:6379")
+        );
+        assert_eq!(
+            Address::parse("redis://localhost:6379"),
+            Address::Tcp("localhost:6379")
+        );
+        assert_eq!(
+            Address::parse("rediss://localhost:6380"),
+            Address::Tls("localhost:6380")
+        );
+        assert_eq!(
+            Address::parse("unix:///var/run/redis.sock"),
+            Address::Unix("/var/run/redis.sock")
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Stand-ins of redis servers shared by the tests
+#![allow(dead_code)]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Listen on a port picked by the system
+pub async fn bind() -> (TcpListener, u16) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    (listener, port)
+}
+
+/// Find a port nobody is listening on
+pub fn free_port() -> u16 {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+/// Reply PONG to every read on `stream` after `delay`
+pub async fn reply_pong<S>(mut stream: S, delay: Duration)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut buf = [0; 1024];
+    while let Ok(n) = stream.read(&mut buf).await {
+        if n == 0 {
+            break;
+        }
+        tokio::time::delay_for(delay).await;
+        if stream.write_all(b"+PONG\r\n").await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Stand-in of a slow redis server which replies PONG to every read after
+/// `delay`, counting the connections
+pub async fn slow_server(delay: Duration) -> (u16, Arc<AtomicUsize>) {
+    let (listener, port) = bind().await;
+    (port, serve_pong(listener, delay))
+}
+
+/// Stand-in of a redis server which replies PONG to every read
+pub async fn pong_server() -> u16 {
+    let (listener, port) = bind().await;
+    serve_pong(listener, Duration::from_secs(0));
+    port
+}
+
+/// Stand-in of a redis server which replies PONG to every read on `port`
+pub async fn pong_server_at(port: u16) {
+    let listener = TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+    serve_pong(listener, Duration::from_secs(0));
+}
+
+fn serve_pong(mut listener: TcpListener, delay: Duration) -> Arc<AtomicUsize> {
+    let connections = Arc::new(AtomicUsize::new(0));
+
+    let counter = connections.clone();
+    actix_rt::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            counter.fetch_add(1, Ordering::SeqCst);
+            actix_rt::spawn(reply_pong(stream, delay));
+        }
+    });
+
+    connections
+}
+
+/// Stand-in of a stalled redis server, or a half-open connection, which
+/// never replies
+pub async fn stalled_server() -> u16 {
+    let (mut listener, port) = bind().await;
+
+    actix_rt::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            actix_rt::spawn(async move {
+                let mut buf = [0; 1024];
+                while let Ok(n) = stream.read(&mut buf).await {
+                    if n == 0 {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    port
+}
+
+/// Stand-in of a redis server answering PING with the number of the
+/// connection, and never answering ECHO
+pub async fn numbering_server() -> u16 {
+    let (mut listener, port) = bind().await;
+
+    actix_rt::spawn(async move {
+        let mut number = 0;
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let reply = format!("+{}\r\n", number);
+            number += 1;
+            actix_rt::spawn(async move {
+                let mut buf = [0; 1024];
+                while let Ok(n) = stream.read(&mut buf).await {
+                    if n == 0 {
+                        break;
+                    }
+                    if String::from_utf8_lossy(&buf[..n]).contains("ECHO") {
+                        continue;
+                    }
+                    if stream.write_all(reply.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    port
+}
+
+/// Stand-in of a redis server which closes the first connection right away
+/// and keeps the next ones open
+pub async fn closing_server() -> u16 {
+    let (mut listener, port) = bind().await;
+
+    actix_rt::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        tokio::time::delay_for(Duration::from_millis(100)).await;
+        drop(stream);
+        let mut streams = vec![];
+        loop {
+            streams.push(listener.accept().await.unwrap());
+        }
+    });
+
+    port
+}
@@ -0,0 +1,38 @@
+use actix_redis::{command::*, ConnectOptions, Error, RedisActor};
+
+// the server at 6380 is started with `--requirepass actix`
+#[actix_rt::test]
+async fn test_auth() {
+    env_logger::init();
+
+    let addr = RedisActor::start_with(
+        "127.0.0.1:6380",
+        ConnectOptions {
+            password: Some("actix".into()),
+            ..ConnectOptions::default()
+        },
+    );
+
+    let res = addr.send(Ping(None)).await;
+    match res {
+        Ok(Ok(pong)) => assert_eq!(pong, "PONG"),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_auth_failure() {
+    let addr = RedisActor::start_with(
+        "127.0.0.1:6380",
+        ConnectOptions {
+            password: Some("wrong password".into()),
+            ..ConnectOptions::default()
+        },
+    );
+
+    let res = addr.send(Ping(None)).await;
+    match res {
+        Ok(Err(Error::Auth(_))) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use actix_redis::redis::Overload;
+use actix_redis::{command::*, Error, RedisActor};
+use futures::future;
+
+mod common;
+use common::slow_server;
+
+#[actix_rt::test]
+async fn test_max_in_flight_fail_fast() {
+    let (port, _) = slow_server(Duration::from_millis(200)).await;
+    let addr = RedisActor::builder()
+        .max_in_flight(1, Overload::FailFast)
+        .start(format!("127.0.0.1:{}", port));
+
+    let pending = addr.send(Ping(None));
+    tokio::time::delay_for(Duration::from_millis(50)).await;
+
+    let res = addr.send(Ping(None)).await;
+    match res {
+        Ok(Err(Error::Overloaded)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    let res = pending.await;
+    match res {
+        Ok(Ok(_)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+    // room again once the reply arrived
+    let res = addr.send(Ping(None)).await;
+    match res {
+        Ok(Ok(_)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_max_in_flight_wait() {
+    let (port, _) = slow_server(Duration::from_millis(50)).await;
+    let addr = RedisActor::builder()
+        .max_in_flight(1, Overload::Wait)
+        .start(format!("127.0.0.1:{}", port));
+
+    // the server replies once per read, so the requests have to be written
+    // one at a time to get all replies
+    let res = future::join_all((0..3).map(|_| addr.send(Ping(None)))).await;
+    for res in res {
+        match res {
+            Ok(Ok(_)) => (),
+            _ => panic!("Should not happen {:?}", res),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::time::{Duration, Instant};
+
+use actix_redis::{command::*, Error, RedisActor};
+use futures::channel::mpsc;
+use futures::StreamExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+mod common;
+use common::bind;
+
+// stand-in of a redis server which answers PING and never serves BLPOP,
+// reporting connections closed while blocked
+async fn blocking_server() -> (u16, mpsc::UnboundedReceiver<()>) {
+    let (mut listener, port) = bind().await;
+    let (tx, rx) = mpsc::unbounded();
+
+    actix_rt::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let tx = tx.clone();
+            actix_rt::spawn(async move {
+                let mut buf = [0; 1024];
+                let mut blocked = false;
+                while let Ok(n) = stream.read(&mut buf).await {
+                    if n == 0 {
+                        break;
+                    }
+                    if String::from_utf8_lossy(&buf[..n]).contains("BLPOP") {
+                        blocked = true;
+                    } else if stream.write_all(b"+PONG\r\n").await.is_err() {
+                        break;
+                    }
+                }
+                if blocked {
+                    let _ = tx.unbounded_send(());
+                }
+            });
+        }
+    });
+
+    (port, rx)
+}
+
+fn blpop(timeout: Duration) -> BLPop {
+    BLPop {
+        keys: vec!["test-blocking".into()],
+        timeout,
+    }
+}
+
+#[actix_rt::test]
+async fn test_blocking_does_not_stall_queue() {
+    let (port, _closed) = blocking_server().await;
+    let addr = RedisActor::start(format!("127.0.0.1:{}", port));
+
+    let blocked = addr.send(blpop(Duration::from_secs(0)));
+
+    let res = addr.send(Ping(None)).await;
+    match res {
+        Ok(Ok(ref pong)) if pong == "PONG" => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+    drop(blocked);
+}
+
+#[actix_rt::test]
+async fn test_blocking_timeout() {
+    let (port, _closed) = blocking_server().await;
+    let addr = RedisActor::builder()
+        .response_timeout(Duration::from_millis(100))
+        .start(format!("127.0.0.1:{}", port));
+
+    // the response timeout starts once the server stops blocking
+    let start = Instant::now();
+    let res = addr.send(blpop(Duration::from_millis(300))).await;
+    match res {
+        Ok(Err(Error::Timeout)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+    assert!(start.elapsed() >= Duration::from_millis(400));
+}
+
+#[actix_rt::test]
+async fn test_blocking_cancel() {
+    let (port, mut closed) = blocking_server().await;
+    let addr = RedisActor::start(format!("127.0.0.1:{}", port));
+
+    let blocked = addr.send(blpop(Duration::from_secs(0)));
+    let res = tokio::time::timeout(Duration::from_millis(200), blocked).await;
+    assert!(res.is_err());
+
+    // dropping the request closes the connection of the command
+    let res = tokio::time::timeout(Duration::from_secs(1), closed.next()).await;
+    match res {
+        Ok(Some(())) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
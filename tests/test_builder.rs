@@ -0,0 +1,41 @@
+#[macro_use]
+extern crate redis_async;
+
+use std::time::Duration;
+
+use actix_redis::{redis, Error, RedisActor, RespValue};
+
+#[actix_rt::test]
+async fn test_builder() {
+    env_logger::init();
+
+    let addr = RedisActor::builder()
+        .connect_timeout(Duration::from_secs(1))
+        .backoff_initial_interval(Duration::from_millis(100))
+        .backoff_max_interval(Duration::from_secs(1))
+        .nodelay(true)
+        .keepalive(Some(Duration::from_secs(60)))
+        .client_name("actix-redis-test")
+        .start("127.0.0.1:6379");
+
+    let res = addr
+        .send(redis::Command(resp_array!["CLIENT", "GETNAME"]))
+        .await;
+    match res {
+        Ok(Ok(RespValue::BulkString(name))) => assert_eq!(name, b"actix-redis-test"),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_builder_max_elapsed_time() {
+    let addr = RedisActor::builder()
+        .backoff_max_elapsed_time(Some(Duration::from_millis(1)))
+        .start("localhost:54000");
+
+    let res = addr.send(redis::Command(resp_array!["PING"])).await;
+    match res {
+        Ok(Err(Error::NotConnected)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
@@ -0,0 +1,141 @@
+use actix_redis::pipeline::Pipeline;
+use actix_redis::transaction::{CheckAndSet, Transaction};
+use actix_redis::{command::*, RedisClusterActor, RespValue};
+use futures::FutureExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+mod common;
+use common::bind;
+
+// names of the commands in `buf`, assuming their arguments are not arrays
+fn commands(buf: &[u8]) -> Vec<String> {
+    let buf = String::from_utf8_lossy(buf).to_uppercase();
+    let lines: Vec<&str> = buf.split("\r\n").collect();
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.starts_with('*'))
+        .filter_map(|(i, _)| lines.get(i + 2).map(|name| name.to_string()))
+        .collect()
+}
+
+// stand-in of a cluster node. The owner of all slots redirects every command
+// to the importing node with ASK, which only serves commands right after
+// ASKING succeeded, replying "value" to GET.
+fn serve(
+    mut listener: TcpListener,
+    slots: String,
+    importing: Option<u16>,
+    asked: &'static str,
+) {
+    actix_rt::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let slots = slots.clone();
+            actix_rt::spawn(async move {
+                let mut asking = false;
+                let mut buf = [0; 1024];
+                while let Ok(n) = stream.read(&mut buf).await {
+                    if n == 0 {
+                        break;
+                    }
+                    let mut reply = String::new();
+                    for cmd in commands(&buf[..n]) {
+                        if cmd == "CLUSTER" {
+                            reply.push_str(&slots);
+                        } else if cmd == "ASKING" {
+                            reply.push_str(asked);
+                        } else if let Some(port) = importing {
+                            reply.push_str(&format!("-ASK 0 127.0.0.1:{}\r\n", port));
+                        } else if asking {
+                            reply.push_str(match cmd.as_str() {
+                                "WATCH" | "MULTI" => "+OK\r\n",
+                                "SET" => "+QUEUED\r\n",
+                                "EXEC" => "*1\r\n+OK\r\n",
+                                _ => "$5\r\nvalue\r\n",
+                            });
+                        } else {
+                            reply.push_str("-ERR not asking\r\n");
+                        }
+                        asking = cmd == "ASKING" && asked == "+OK\r\n";
+                    }
+                    if stream.write_all(reply.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+}
+
+// a node owning all slots which are being imported by another one,
+// returning the address of the owner
+async fn migrating() -> String {
+    migrating_to("+OK\r\n").await
+}
+
+// same as `migrating`, the importing node replying `asked` to ASKING
+async fn migrating_to(asked: &'static str) -> String {
+    let (owner, owner_port) = bind().await;
+    let (importing, importing_port) = bind().await;
+    let slots = format!(
+        "*1\r\n*3\r\n:0\r\n:16383\r\n*2\r\n$9\r\n127.0.0.1\r\n:{}\r\n",
+        owner_port
+    );
+    serve(owner, slots.clone(), Some(importing_port), asked);
+    serve(importing, slots, None, asked);
+    format!("127.0.0.1:{}", owner_port)
+}
+
+#[actix_rt::test]
+async fn test_ask_redirection() {
+    let addr = RedisClusterActor::start(migrating().await);
+
+    let res = addr.send(Get { key: "test".into() }).await;
+    match res {
+        Ok(Ok(Some(value))) => assert_eq!(value, b"value"),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_check_and_set_ask_redirection() {
+    let addr = RedisClusterActor::start(migrating().await);
+
+    let cas = CheckAndSet::new(vec!["test".into()], |watcher| {
+        async move {
+            let value = watcher.send(Get { key: "test".into() }).await?;
+            assert_eq!(value, Some(b"value".to_vec()));
+            let mut tx = Transaction::new();
+            tx.add(Set {
+                key: "test".into(),
+                value: "other".into(),
+                expiration: Expiration::Infinite,
+            });
+            Ok(tx)
+        }
+        .boxed_local()
+    });
+    let res = addr.send(cas).await;
+    match res {
+        Ok(Ok(Some(_))) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_pipeline_asking_refused() {
+    let addr = RedisClusterActor::start(migrating_to("-ERR refused\r\n").await);
+
+    let mut pipeline = Pipeline::new();
+    pipeline.add(Get { key: "test".into() });
+    let res = addr.send(pipeline).await;
+    match res {
+        Ok(Ok(replies)) => match replies.into_inner().as_slice() {
+            [RespValue::Error(e)] => assert_eq!(e, "ERR refused"),
+            replies => panic!("Should not happen {:?}", replies),
+        },
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
@@ -0,0 +1,80 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_redis::{command::*, RedisClusterActor};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+mod common;
+use common::bind;
+
+// stand-in of a single node cluster counting CLUSTER SLOTS, which redirects
+// every other command to itself
+async fn moving_node() -> (String, Arc<AtomicUsize>) {
+    let (mut listener, port) = bind().await;
+    let slots = format!(
+        "*1\r\n*3\r\n:0\r\n:16383\r\n*2\r\n$9\r\n127.0.0.1\r\n:{}\r\n",
+        port
+    );
+    let moved = format!("-MOVED 0 127.0.0.1:{}\r\n", port);
+    let refreshes = Arc::new(AtomicUsize::new(0));
+
+    let counter = refreshes.clone();
+    actix_rt::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let (slots, moved, counter) =
+                (slots.clone(), moved.clone(), counter.clone());
+            actix_rt::spawn(async move {
+                let mut buf = [0; 1024];
+                while let Ok(n) = stream.read(&mut buf).await {
+                    let req = String::from_utf8_lossy(&buf[..n]).to_uppercase();
+                    let reply = if n == 0 {
+                        break;
+                    } else if req.contains("SLOTS") {
+                        counter.fetch_add(1, Ordering::SeqCst);
+                        &slots
+                    } else {
+                        &moved
+                    };
+                    if stream.write_all(reply.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    (format!("127.0.0.1:{}", port), refreshes)
+}
+
+#[actix_rt::test]
+async fn test_periodic_refresh() {
+    let (node, refreshes) = moving_node().await;
+    let _addr = RedisClusterActor::builder()
+        .refresh_interval(Duration::from_millis(100))
+        .min_refresh_interval(Duration::from_millis(0))
+        .start(node);
+
+    tokio::time::delay_for(Duration::from_millis(450)).await;
+    assert!(refreshes.load(Ordering::SeqCst) >= 3);
+}
+
+#[actix_rt::test]
+async fn test_debounced_refresh() {
+    let (node, refreshes) = moving_node().await;
+    let addr = RedisClusterActor::builder()
+        .min_refresh_interval(Duration::from_millis(500))
+        .start(node);
+
+    // every redirection asks for a refresh
+    let res = addr.send(Get { key: "test".into() }).await;
+    match res {
+        Ok(Err(_)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+    assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+
+    tokio::time::delay_for(Duration::from_millis(600)).await;
+    assert_eq!(refreshes.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use actix_redis::cluster::ReadPolicy;
+use actix_redis::{command::*, RedisClusterActor};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+mod common;
+use common::bind;
+
+// stand-in of a cluster node which replies its `name` to GET, and PONG after
+// `latency`. A replica refuses GET until READONLY is sent over the
+// connection, and refuses SET.
+fn serve(
+    mut listener: TcpListener,
+    name: &'static str,
+    replica: bool,
+    latency: Duration,
+    slots: String,
+) {
+    actix_rt::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let slots = slots.clone();
+            actix_rt::spawn(async move {
+                let mut readonly = false;
+                let mut buf = [0; 1024];
+                while let Ok(n) = stream.read(&mut buf).await {
+                    let req = String::from_utf8_lossy(&buf[..n]).to_uppercase();
+                    let reply = if n == 0 {
+                        break;
+                    } else if req.contains("SLOTS") {
+                        slots.clone()
+                    } else if req.contains("PING") {
+                        tokio::time::delay_for(latency).await;
+                        "+PONG\r\n".to_string()
+                    } else if req.contains("READONLY") {
+                        readonly = true;
+                        "+OK\r\n".to_string()
+                    } else if req.contains("GET") && (readonly || !replica) {
+                        format!("${}\r\n{}\r\n", name.len(), name)
+                    } else if req.contains("SET") && !replica {
+                        "+OK\r\n".to_string()
+                    } else {
+                        "-MOVED 0 127.0.0.1:1\r\n".to_string()
+                    };
+                    if stream.write_all(reply.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+}
+
+// a master and its replica serving all slots, returning the address of the
+// master. The replica is the nearest.
+async fn cluster() -> String {
+    let (master, master_port) = bind().await;
+    let (replica, replica_port) = bind().await;
+    let slots = format!(
+        "*1\r\n*4\r\n:0\r\n:16383\r\n*2\r\n$9\r\n127.0.0.1\r\n:{}\r\n*2\r\n$9\r\n127.0.0.1\r\n:{}\r\n",
+        master_port, replica_port
+    );
+    serve(
+        master,
+        "master",
+        false,
+        Duration::from_millis(100),
+        slots.clone(),
+    );
+    serve(replica, "replica", true, Duration::from_millis(0), slots);
+    format!("127.0.0.1:{}", master_port)
+}
+
+async fn read_from(policy: ReadPolicy) -> Vec<u8> {
+    let addr = RedisClusterActor::builder()
+        .read_policy(policy)
+        .start(cluster().await);
+
+    let res = addr
+        .send(Set {
+            key: "test".into(),
+            value: "value".into(),
+            expiration: Expiration::Infinite,
+        })
+        .await;
+    match res {
+        Ok(Ok(())) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    let res = addr.send(Get { key: "test".into() }).await;
+    match res {
+        Ok(Ok(Some(value))) => value,
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_read_from_master() {
+    assert_eq!(read_from(ReadPolicy::Master).await, b"master");
+}
+
+#[actix_rt::test]
+async fn test_read_from_replica() {
+    assert_eq!(read_from(ReadPolicy::PreferReplica).await, b"replica");
+    assert_eq!(read_from(ReadPolicy::RoundRobinReplicas).await, b"replica");
+}
+
+#[actix_rt::test]
+async fn test_read_from_nearest() {
+    let addr = RedisClusterActor::builder()
+        .read_policy(ReadPolicy::Nearest)
+        .start(cluster().await);
+
+    // the latencies are measured once the slots are known
+    tokio::time::delay_for(Duration::from_millis(300)).await;
+
+    let res = addr.send(Get { key: "test".into() }).await;
+    match res {
+        Ok(Ok(Some(value))) => assert_eq!(value, b"replica"),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
@@ -0,0 +1,100 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_redis::{command::*, RedisActor, RedisClusterActor};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+mod common;
+use common::{bind, free_port};
+
+// stand-in of a single node cluster which replies "value" to GET
+async fn single_node() -> u16 {
+    let (mut listener, port) = bind().await;
+    let slots = format!(
+        "*1\r\n*3\r\n:0\r\n:16383\r\n*2\r\n$9\r\n127.0.0.1\r\n:{}\r\n",
+        port
+    );
+
+    actix_rt::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let slots = slots.clone();
+            actix_rt::spawn(async move {
+                let mut buf = [0; 1024];
+                while let Ok(n) = stream.read(&mut buf).await {
+                    let req = String::from_utf8_lossy(&buf[..n]).to_uppercase();
+                    let reply = if n == 0 {
+                        break;
+                    } else if req.contains("SLOTS") {
+                        slots.clone()
+                    } else {
+                        "$5\r\nvalue\r\n".to_string()
+                    };
+                    if stream.write_all(reply.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    port
+}
+
+// stand-in of a node which is not part of the cluster any more, closing the
+// connections right away and counting them
+async fn leaving_node() -> (u16, Arc<AtomicUsize>) {
+    let (mut listener, port) = bind().await;
+    let connections = Arc::new(AtomicUsize::new(0));
+
+    let counter = connections.clone();
+    actix_rt::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            counter.fetch_add(1, Ordering::SeqCst);
+            drop(stream);
+        }
+    });
+
+    (port, connections)
+}
+
+#[actix_rt::test]
+async fn test_cluster_seeds() {
+    let port = single_node().await;
+    let addr = RedisClusterActor::start_seeds(vec![
+        format!("127.0.0.1:{}", free_port()),
+        format!("127.0.0.1:{}", port),
+    ]);
+
+    let res = addr.send(Get { key: "test".into() }).await;
+    match res {
+        Ok(Ok(Some(value))) => assert_eq!(value, b"value"),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_cluster_seeds_not_kept() {
+    let (left, connections) = leaving_node().await;
+    let port = single_node().await;
+    let addr = RedisClusterActor::builder()
+        .connection(
+            RedisActor::builder().backoff_initial_interval(Duration::from_millis(50)),
+        )
+        .start_seeds(vec![
+            format!("127.0.0.1:{}", left),
+            format!("127.0.0.1:{}", port),
+        ]);
+
+    let res = addr.send(Get { key: "test".into() }).await;
+    match res {
+        Ok(Ok(Some(value))) => assert_eq!(value, b"value"),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    // the seed was asked for the slots once, and is not reconnected to
+    tokio::time::delay_for(Duration::from_millis(500)).await;
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,63 @@
+use std::collections::HashSet;
+
+use actix_redis::pubsub::{Listener, SSubscribe, SUnsubscribe};
+use actix_redis::{command::*, slot::hash_slot, RedisClusterActor};
+use futures::StreamExt;
+
+#[actix_rt::test]
+async fn test_cluster_sharded_pubsub() {
+    env_logger::init();
+
+    let addr = RedisClusterActor::start("127.0.0.1:7000");
+
+    // channels of different slots, served by different nodes
+    let channels: Vec<String> = (0..8).map(|i| format!("test-sharded-{}", i)).collect();
+    let slots: HashSet<_> = channels.iter().map(|c| hash_slot(c.as_bytes())).collect();
+    assert!(slots.len() > 1);
+
+    let (listener, mut messages) = Listener::stream();
+    let res = addr
+        .send(SSubscribe {
+            channels: channels.clone(),
+            listener,
+        })
+        .await;
+    match res {
+        Ok(Ok(())) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    for channel in channels.iter() {
+        let res = addr
+            .send(SPublish {
+                channel: channel.clone(),
+                message: channel.clone(),
+            })
+            .await;
+        match res {
+            Ok(Ok(1)) => (),
+            _ => panic!("Should not happen {:?}", res),
+        }
+
+        let msg = messages.next().await.unwrap();
+        assert_eq!(&msg.channel, channel);
+        assert_eq!(msg.payload, channel.as_bytes());
+    }
+
+    let res = addr.send(SUnsubscribe { channels: vec![] }).await;
+    match res {
+        Ok(Ok(())) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    let res = addr
+        .send(SPublish {
+            channel: channels[0].clone(),
+            message: "hello".into(),
+        })
+        .await;
+    match res {
+        Ok(Ok(0)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
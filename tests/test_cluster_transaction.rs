@@ -0,0 +1,78 @@
+use actix_redis::pipeline::Pipeline;
+use actix_redis::transaction::Transaction;
+use actix_redis::{command::*, Error, RedisClusterActor};
+
+#[actix_rt::test]
+async fn test_cluster_transaction() {
+    env_logger::init();
+
+    let addr = RedisClusterActor::start("127.0.0.1:7000");
+
+    // same slot thanks to the hash tag
+    let mut tx = Transaction::new();
+    tx.add(Set {
+        key: "{test-transaction}:a".into(),
+        value: "a".into(),
+        expiration: Expiration::Infinite,
+    });
+    let get = tx.add(Get {
+        key: "{test-transaction}:a".into(),
+    });
+    tx.add(Del {
+        keys: vec!["{test-transaction}:b".into()],
+    });
+
+    let res = addr.send(tx).await;
+    match res {
+        Ok(Ok(Some(ref replies))) => {
+            assert_eq!(replies.get(&get).unwrap(), Some(b"a".to_vec()));
+        }
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_cluster_transaction_multiple_slot() {
+    let addr = RedisClusterActor::start("127.0.0.1:7000");
+
+    let mut tx = Transaction::new();
+    tx.add(Get { key: "foo".into() });
+    tx.add(Get { key: "bar".into() });
+
+    let res = addr.send(tx).await;
+    match res {
+        Ok(Err(Error::MultipleSlot(_))) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_cluster_pipeline() {
+    let addr = RedisClusterActor::start("127.0.0.1:7000");
+
+    // keys spread over the nodes
+    let keys: Vec<String> = (0..16).map(|i| format!("test-pipeline-{}", i)).collect();
+
+    let mut pipeline = Pipeline::new();
+    for key in keys.iter() {
+        pipeline.add(Set {
+            key: key.clone(),
+            value: key.clone(),
+            expiration: Expiration::Infinite,
+        });
+    }
+    let gets: Vec<_> = keys
+        .iter()
+        .map(|key| pipeline.add(Get { key: key.clone() }))
+        .collect();
+
+    let res = addr.send(pipeline).await;
+    let replies = match res {
+        Ok(Ok(replies)) => replies,
+        _ => panic!("Should not happen {:?}", res),
+    };
+
+    for (key, get) in keys.iter().zip(gets.iter()) {
+        assert_eq!(replies.get(get).unwrap(), Some(key.as_bytes().to_vec()));
+    }
+}
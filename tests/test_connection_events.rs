@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+use actix_redis::redis::{ConnectionEvent, ConnectionListener, ConnectionState, Status};
+use actix_redis::RedisActor;
+use futures::StreamExt;
+
+mod common;
+use common::{closing_server, free_port};
+
+#[actix_rt::test]
+async fn test_connection_events() {
+    let port = closing_server().await;
+    let addr = format!("127.0.0.1:{}", port);
+    let (listener, mut events) = ConnectionListener::stream();
+
+    let redis = RedisActor::builder()
+        .connection_listener(listener)
+        .start(addr.clone());
+
+    assert_eq!(
+        events.next().await,
+        Some(ConnectionEvent::Connected { addr: addr.clone() })
+    );
+    let res = redis.send(Status).await;
+    match res {
+        Ok(ref status) if status.state == ConnectionState::Connected => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    match events.next().await {
+        Some(ConnectionEvent::Disconnected { addr: ref a, .. }) if a == &addr => (),
+        event => panic!("Should not happen {:?}", event),
+    }
+    assert_eq!(
+        events.next().await,
+        Some(ConnectionEvent::Reconnecting {
+            addr: addr.clone(),
+            delay: Duration::from_secs(0),
+        })
+    );
+    assert_eq!(
+        events.next().await,
+        Some(ConnectionEvent::Connected { addr: addr.clone() })
+    );
+
+    let res = redis.send(Status).await;
+    match res {
+        Ok(ref status)
+            if status.state == ConnectionState::Connected
+                && status.last_error.is_some() => {}
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_connection_refused_events() {
+    let addr = format!("127.0.0.1:{}", free_port());
+    let (listener, mut events) = ConnectionListener::stream();
+
+    let redis = RedisActor::builder()
+        .backoff_initial_interval(Duration::from_secs(10))
+        .connection_listener(listener)
+        .start(addr.clone());
+
+    match events.next().await {
+        Some(ConnectionEvent::Disconnected { addr: ref a, .. }) if a == &addr => (),
+        event => panic!("Should not happen {:?}", event),
+    }
+    match events.next().await {
+        Some(ConnectionEvent::Reconnecting { addr: ref a, .. }) if a == &addr => (),
+        event => panic!("Should not happen {:?}", event),
+    }
+
+    let res = redis.send(Status).await;
+    match res {
+        Ok(ref status)
+            if status.state == ConnectionState::Connecting
+                && status.last_error.is_some()
+                && status.buffered == 0 => {}
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
@@ -0,0 +1,132 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_redis::keyspace::{
+    KeyspaceChannel, KeyspaceEvent, KeyspaceListener, SubscribeKeyspace,
+};
+use actix_redis::{command::*, RedisActor, RedisSubscriber};
+use futures::StreamExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+mod common;
+use common::bind;
+
+// stand-in of a redis server recording CONFIG and PSUBSCRIBE, which drops
+// the first subscribed connection
+async fn recording_server() -> (u16, Arc<Mutex<Vec<String>>>) {
+    let (mut listener, port) = bind().await;
+    let commands = Arc::new(Mutex::new(vec![]));
+
+    let recorded = commands.clone();
+    actix_rt::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let recorded = recorded.clone();
+            actix_rt::spawn(async move {
+                let mut buf = [0; 1024];
+                while let Ok(n) = stream.read(&mut buf).await {
+                    let req = String::from_utf8_lossy(&buf[..n]).to_uppercase();
+                    let (cmd, reply) = if n == 0 {
+                        break;
+                    } else if req.contains("CONFIG") {
+                        ("CONFIG", "+OK\r\n")
+                    } else if req.contains("PSUBSCRIBE") {
+                        ("PSUBSCRIBE", "*3\r\n$10\r\npsubscribe\r\n$1\r\nx\r\n:1\r\n")
+                    } else {
+                        continue;
+                    };
+                    let first = {
+                        let mut recorded = recorded.lock().unwrap();
+                        recorded.push(cmd.to_string());
+                        recorded.len() == 2
+                    };
+                    if stream.write_all(reply.as_bytes()).await.is_err() {
+                        break;
+                    }
+                    if first {
+                        tokio::time::delay_for(Duration::from_millis(50)).await;
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    (port, commands)
+}
+
+#[actix_rt::test]
+async fn test_keyspace() {
+    env_logger::init();
+
+    let addr = RedisActor::start("127.0.0.1:6379");
+    let subscriber = RedisSubscriber::start("127.0.0.1:6379");
+
+    let (listener, mut events) = KeyspaceListener::stream();
+    let res = subscriber
+        .send(SubscribeKeyspace {
+            config: Some("KA".into()),
+            channel: KeyspaceChannel::Keyspace,
+            db: Some(0),
+            pattern: "test-keyspace*".into(),
+            listener,
+        })
+        .await;
+    match res {
+        Ok(Ok(())) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    let res = addr
+        .send(Set {
+            key: "test-keyspace".into(),
+            value: "value".into(),
+            expiration: Expiration::Infinite,
+        })
+        .await;
+    match res {
+        Ok(Ok(())) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    assert_eq!(
+        events.next().await,
+        Some(KeyspaceEvent {
+            db: 0,
+            key: "test-keyspace".into(),
+            event: "set".into(),
+        })
+    );
+}
+
+#[actix_rt::test]
+async fn test_keyspace_config_after_reconnect() {
+    let (port, commands) = recording_server().await;
+    let subscriber = RedisSubscriber::start(format!("127.0.0.1:{}", port));
+
+    let (listener, _events) = KeyspaceListener::stream();
+    let res = subscriber
+        .send(SubscribeKeyspace {
+            config: Some("KA".into()),
+            channel: KeyspaceChannel::Keyspace,
+            db: Some(0),
+            pattern: "test-keyspace*".into(),
+            listener,
+        })
+        .await;
+    match res {
+        Ok(Ok(())) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    for _ in 0..50 {
+        if commands.lock().unwrap().len() >= 4 {
+            break;
+        }
+        tokio::time::delay_for(Duration::from_millis(20)).await;
+    }
+    assert_eq!(
+        *commands.lock().unwrap(),
+        vec!["CONFIG", "PSUBSCRIBE", "CONFIG", "PSUBSCRIBE"]
+    );
+}
@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use actix_redis::{command::*, Error, RedisActor};
+
+mod common;
+use common::{free_port, pong_server_at};
+
+#[actix_rt::test]
+async fn test_offline_queue_flush() {
+    let port = free_port();
+
+    let addr = RedisActor::builder()
+        .backoff_initial_interval(Duration::from_millis(50))
+        .backoff_max_interval(Duration::from_millis(50))
+        .offline_queue(16, Duration::from_secs(10))
+        .start(format!("127.0.0.1:{}", port));
+
+    let res = addr.send(Ping(None));
+
+    tokio::time::delay_for(Duration::from_millis(200)).await;
+    pong_server_at(port).await;
+
+    match res.await {
+        Ok(Ok(ref pong)) if pong == "PONG" => (),
+        res => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_offline_queue_max_wait() {
+    let port = free_port();
+
+    let addr = RedisActor::builder()
+        .backoff_initial_interval(Duration::from_millis(50))
+        .backoff_max_interval(Duration::from_millis(50))
+        .offline_queue(16, Duration::from_millis(200))
+        .start(format!("127.0.0.1:{}", port));
+
+    let res = addr.send(Ping(None)).await;
+    match res {
+        Ok(Err(Error::NotConnected)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_offline_queue_max_len() {
+    let port = free_port();
+
+    let addr = RedisActor::builder()
+        .offline_queue(1, Duration::from_secs(10))
+        .start(format!("127.0.0.1:{}", port));
+
+    let _held = addr.send(Ping(None));
+    let res = addr.send(Ping(None)).await;
+    match res {
+        Ok(Err(Error::NotConnected)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+use actix_redis::redis::Overload;
+use actix_redis::{command::*, Error, RedisActor};
+
+mod common;
+use common::stalled_server;
+
+#[actix_rt::test]
+async fn test_ping_silent_peer() {
+    let port = stalled_server().await;
+
+    let addr = RedisActor::builder()
+        .ping_interval(Duration::from_millis(100), Duration::from_millis(100))
+        .start(format!("127.0.0.1:{}", port));
+
+    // no response timeout, only the liveness check fails this request
+    let res = addr.send(Ping(None)).await;
+    match res {
+        Ok(Err(Error::Disconnected)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_ping_silent_peer_in_flight() {
+    let port = stalled_server().await;
+
+    let addr = RedisActor::builder()
+        .ping_interval(Duration::from_millis(100), Duration::from_millis(100))
+        .max_in_flight(1, Overload::Wait)
+        .start(format!("127.0.0.1:{}", port));
+
+    // the liveness check is not held behind the request filling the limit
+    let res = tokio::time::timeout(Duration::from_secs(2), addr.send(Ping(None)))
+        .await
+        .expect("liveness check was not sent");
+    match res {
+        Ok(Err(Error::Disconnected)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
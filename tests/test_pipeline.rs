@@ -0,0 +1,62 @@
+#[macro_use]
+extern crate redis_async;
+
+use actix_redis::pipeline::Pipeline;
+use actix_redis::{command::*, RedisActor, RespValue};
+
+#[actix_rt::test]
+async fn test_pipeline() {
+    env_logger::init();
+
+    let addr = RedisActor::start("127.0.0.1:6379");
+
+    let mut pipeline = Pipeline::new();
+    let set = pipeline.add(Set {
+        key: "test-pipeline".into(),
+        value: "value".into(),
+        expiration: Expiration::Infinite,
+    });
+    let get = pipeline.add(Get {
+        key: "test-pipeline".into(),
+    });
+    let ping = pipeline.add_raw(resp_array!["PING"]);
+    let del = pipeline.add(Del {
+        keys: vec!["test-pipeline".into()],
+    });
+
+    let res = addr.send(pipeline).await;
+    let replies = match res {
+        Ok(Ok(replies)) => replies,
+        _ => panic!("Should not happen {:?}", res),
+    };
+
+    assert_eq!(replies.get(&set).unwrap(), ());
+    assert_eq!(replies.get(&get).unwrap(), Some(b"value".to_vec()));
+    assert_eq!(replies.get(&del).unwrap(), 1);
+    assert_eq!(
+        replies.into_inner()[ping],
+        RespValue::SimpleString("PONG".into())
+    );
+}
+
+#[actix_rt::test]
+async fn test_pipeline_command_error() {
+    let addr = RedisActor::start("127.0.0.1:6379");
+
+    let mut pipeline = Pipeline::new();
+    let bad = pipeline.add_raw(resp_array!["NO-SUCH-COMMAND"]);
+    let ping = pipeline.add(Ping(None));
+
+    let res = addr.send(pipeline).await;
+    let replies = match res {
+        Ok(Ok(replies)) => replies,
+        _ => panic!("Should not happen {:?}", res),
+    };
+
+    // a failing command does not affect the others
+    assert_eq!(replies.get(&ping).unwrap(), "PONG");
+    match replies.into_inner()[bad] {
+        RespValue::Error(_) => (),
+        ref res => panic!("Should not happen {:?}", res),
+    }
+}
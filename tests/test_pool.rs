@@ -0,0 +1,51 @@
+#[macro_use]
+extern crate redis_async;
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use actix_redis::pool::{Pool, Selection};
+use actix_redis::{command::*, redis, RedisActor};
+
+mod common;
+use common::numbering_server;
+
+async fn ping(pool: &Pool) -> String {
+    let res = tokio::time::timeout(Duration::from_secs(1), pool.send(Ping(None))).await;
+    match res {
+        Ok(Ok(Ok(number))) => number,
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_pool_round_robin() {
+    let port = numbering_server().await;
+    let pool = Pool::start(format!("127.0.0.1:{}", port), 3);
+
+    let mut connections = HashSet::new();
+    for _ in 0..3 {
+        connections.insert(ping(&pool).await);
+    }
+    assert_eq!(connections.len(), 3);
+}
+
+#[actix_rt::test]
+async fn test_pool_least_pending() {
+    let port = numbering_server().await;
+    let pool = Pool::builder()
+        .size(2)
+        .selection(Selection::LeastPending)
+        .connection(RedisActor::builder())
+        .start(format!("127.0.0.1:{}", port));
+
+    // stalls one of the connections
+    let stalled = pool.send(redis::Command(resp_array!["ECHO", "stalled"]));
+
+    let mut connections = HashSet::new();
+    for _ in 0..4 {
+        connections.insert(ping(&pool).await);
+    }
+    assert_eq!(connections.len(), 1);
+    drop(stalled);
+}
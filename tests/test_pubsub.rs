@@ -0,0 +1,118 @@
+#[macro_use]
+extern crate redis_async;
+
+use std::time::Duration;
+
+use actix_redis::pubsub::{Listener, PSubscribe, Subscribe, Unsubscribe};
+use actix_redis::{command::*, redis, RedisActor, RedisSubscriber};
+use futures::StreamExt;
+
+#[actix_rt::test]
+async fn test_subscribe() {
+    env_logger::init();
+
+    let publisher = RedisActor::start("127.0.0.1:6379");
+    let subscriber = RedisSubscriber::start("127.0.0.1:6379");
+
+    let (listener, mut messages) = Listener::stream();
+    let res = subscriber
+        .send(Subscribe {
+            channels: vec!["test-pubsub".into()],
+            listener,
+        })
+        .await;
+    match res {
+        Ok(Ok(())) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    let (listener, mut pmessages) = Listener::stream();
+    let res = subscriber
+        .send(PSubscribe {
+            patterns: vec!["test-pubsub*".into()],
+            listener,
+        })
+        .await;
+    match res {
+        Ok(Ok(())) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    let res = publisher
+        .send(Publish {
+            channel: "test-pubsub".into(),
+            message: "hello".into(),
+        })
+        .await;
+    match res {
+        Ok(Ok(2)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    let msg = messages.next().await.unwrap();
+    assert_eq!(msg.channel, "test-pubsub");
+    assert_eq!(msg.pattern, None);
+    assert_eq!(msg.payload, b"hello");
+
+    let msg = pmessages.next().await.unwrap();
+    assert_eq!(msg.channel, "test-pubsub");
+    assert_eq!(msg.pattern, Some("test-pubsub*".to_string()));
+    assert_eq!(msg.payload, b"hello");
+
+    let res = subscriber
+        .send(Unsubscribe {
+            channels: vec!["test-pubsub".into()],
+        })
+        .await;
+    match res {
+        Ok(Ok(())) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+    assert!(messages.next().await.is_none());
+}
+
+#[actix_rt::test]
+async fn test_resubscribe() {
+    let publisher = RedisActor::start("127.0.0.1:6379");
+    let subscriber = RedisSubscriber::start("127.0.0.1:6379");
+
+    let (listener, mut messages) = Listener::stream();
+    let res = subscriber
+        .send(Subscribe {
+            channels: vec!["test-resubscribe".into()],
+            listener,
+        })
+        .await;
+    match res {
+        Ok(Ok(())) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    let res = publisher
+        .send(redis::Command(resp_array![
+            "CLIENT", "KILL", "TYPE", "pubsub"
+        ]))
+        .await;
+    match res {
+        Ok(Ok(_)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    // publish until the subscriber is back
+    loop {
+        let res = publisher
+            .send(Publish {
+                channel: "test-resubscribe".into(),
+                message: "hello".into(),
+            })
+            .await;
+        match res {
+            Ok(Ok(0)) => tokio::time::delay_for(Duration::from_millis(100)).await,
+            Ok(Ok(_)) => break,
+            _ => panic!("Should not happen {:?}", res),
+        }
+    }
+
+    let msg = messages.next().await.unwrap();
+    assert_eq!(msg.payload, b"hello");
+}
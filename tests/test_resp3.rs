@@ -0,0 +1,95 @@
+#[macro_use]
+extern crate redis_async;
+
+use actix::prelude::*;
+use actix_redis::redis::{Command, Push, Resp3Command};
+use actix_redis::{RedisActor, Resp3Value, RespValue};
+use futures::channel::mpsc;
+use futures::StreamExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+mod common;
+use common::bind;
+
+// stand-in of a redis server speaking RESP3, which pushes an invalidation
+// message before every reply after the handshake
+async fn resp3_server() -> u16 {
+    let (mut listener, port) = bind().await;
+
+    actix_rt::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = [0; 1024];
+
+        let n = stream.read(&mut buf).await.unwrap();
+        assert!(buf[..n].windows(5).any(|w| w == b"HELLO"));
+        stream
+            .write_all(b"%1\r\n$5\r\nproto\r\n:3\r\n")
+            .await
+            .unwrap();
+
+        while let Ok(n) = stream.read(&mut buf).await {
+            if n == 0 {
+                break;
+            }
+            stream
+                .write_all(b">2\r\n$10\r\ninvalidate\r\n*1\r\n$3\r\nfoo\r\n")
+                .await
+                .unwrap();
+            stream.write_all(b"%1\r\n+foo\r\n#t\r\n").await.unwrap();
+        }
+    });
+
+    port
+}
+
+struct Collector(mpsc::UnboundedSender<Push>);
+
+impl Actor for Collector {
+    type Context = Context<Self>;
+}
+
+impl Handler<Push> for Collector {
+    type Result = ();
+
+    fn handle(&mut self, msg: Push, _: &mut Self::Context) {
+        let _ = self.0.unbounded_send(msg);
+    }
+}
+
+#[actix_rt::test]
+async fn test_resp3() {
+    let port = resp3_server().await;
+    let (tx, mut rx) = mpsc::unbounded();
+    let collector = Collector(tx).start();
+
+    let addr = RedisActor::builder()
+        .resp3(true)
+        .push_recipient(collector.recipient())
+        .start(format!("127.0.0.1:{}", port));
+
+    let res = addr.send(Resp3Command(resp_array!["GET", "foo"])).await;
+    match res {
+        Ok(Ok(Resp3Value::Map(ref map)))
+            if map
+                == &[(
+                    Resp3Value::SimpleString("foo".into()),
+                    Resp3Value::Boolean(true),
+                )] => {}
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    match rx.next().await {
+        Some(Push(ref data))
+            if data[0] == Resp3Value::BulkString(b"invalidate".to_vec()) => {}
+        push => panic!("Should not happen {:?}", push),
+    }
+
+    // RESP2 callers get the closest RESP2 value
+    let res = addr.send(Command(resp_array!["GET", "foo"])).await;
+    match res {
+        Ok(Ok(RespValue::Array(ref values)))
+            if values
+                == &[RespValue::SimpleString("foo".into()), RespValue::Integer(1)] => {}
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
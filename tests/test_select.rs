@@ -0,0 +1,47 @@
+use actix_redis::{command::*, ConnectOptions, RedisActor};
+
+#[actix_rt::test]
+async fn test_select() {
+    env_logger::init();
+
+    let db0 = RedisActor::start("127.0.0.1:6379");
+    let db1 = RedisActor::start_with(
+        "127.0.0.1:6379",
+        ConnectOptions {
+            db: 1,
+            ..ConnectOptions::default()
+        },
+    );
+
+    let res = db1
+        .send(Set {
+            key: "test-select".into(),
+            value: "value".into(),
+            expiration: Expiration::Infinite,
+        })
+        .await;
+    match res {
+        Ok(Ok(())) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    let res = db0
+        .send(Del {
+            keys: vec!["test-select".into()],
+        })
+        .await;
+    match res {
+        Ok(Ok(_)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    let res = db1
+        .send(Get {
+            key: "test-select".into(),
+        })
+        .await;
+    match res {
+        Ok(Ok(Some(resp))) => assert_eq!(resp, b"value"),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
@@ -0,0 +1,217 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_redis::redis::Status;
+use actix_redis::{command::*, Error, RedisActor, Sentinel};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+mod common;
+use common::{bind, pong_server};
+
+fn bulk(s: &str) -> String {
+    format!("${}\r\n{}\r\n", s.len(), s)
+}
+
+// announce the changes of `master` to a subscribed connection
+async fn announce(mut stream: TcpStream, master: Arc<AtomicUsize>) {
+    let reply = format!(
+        "*3\r\n{}{}:1\r\n",
+        bulk("subscribe"),
+        bulk("+switch-master")
+    );
+    stream.write_all(reply.as_bytes()).await.unwrap();
+
+    let mut last = master.load(Ordering::SeqCst);
+    loop {
+        tokio::time::delay_for(Duration::from_millis(20)).await;
+        let port = master.load(Ordering::SeqCst);
+        if port == last {
+            continue;
+        }
+        let payload = format!("mymaster 127.0.0.1 {} 127.0.0.1 {}", last, port);
+        let msg = format!(
+            "*3\r\n{}{}{}",
+            bulk("message"),
+            bulk("+switch-master"),
+            bulk(&payload)
+        );
+        if stream.write_all(msg.as_bytes()).await.is_err() {
+            break;
+        }
+        last = port;
+    }
+}
+
+// stand-in of a sentinel which knows `mymaster` at the port in `master`
+async fn sentinel_server(master: Arc<AtomicUsize>) -> u16 {
+    let (mut listener, port) = bind().await;
+
+    actix_rt::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let master = master.clone();
+            actix_rt::spawn(async move {
+                let mut buf = [0; 1024];
+                while let Ok(n) = stream.read(&mut buf).await {
+                    let req = String::from_utf8_lossy(&buf[..n]).to_uppercase();
+                    if n == 0 {
+                        break;
+                    } else if req.contains("SUBSCRIBE") {
+                        announce(stream, master).await;
+                        break;
+                    } else if req.contains("MYMASTER") {
+                        let port = master.load(Ordering::SeqCst).to_string();
+                        let reply =
+                            format!("*2\r\n{}{}", bulk("127.0.0.1"), bulk(&port));
+                        let _ = stream.write_all(reply.as_bytes()).await;
+                    } else {
+                        let _ = stream.write_all(b"$-1\r\n").await;
+                    }
+                }
+            });
+        }
+    });
+
+    port
+}
+
+// stand-in of a sentinel which answers a single query for `mymaster` and
+// stops listening right away
+async fn dying_sentinel(master: u16) -> u16 {
+    let (mut listener, port) = bind().await;
+
+    actix_rt::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        drop(listener);
+        let mut buf = [0; 1024];
+        let _ = stream.read(&mut buf).await;
+        let reply = format!("*2\r\n{}{}", bulk("127.0.0.1"), bulk(&master.to_string()));
+        let _ = stream.write_all(reply.as_bytes()).await;
+    });
+
+    port
+}
+
+#[actix_rt::test]
+async fn test_sentinel_failover() {
+    let first = pong_server().await;
+    let second = pong_server().await;
+    let master = Arc::new(AtomicUsize::new(first as usize));
+    let sentinel = sentinel_server(master.clone()).await;
+
+    let addr = RedisActor::start_sentinel(Sentinel::new(
+        "mymaster",
+        vec![format!("127.0.0.1:{}", sentinel)],
+    ));
+
+    let res = addr.send(Ping(None)).await;
+    match res {
+        Ok(Ok(_)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+    let res = addr.send(Status).await;
+    match res {
+        Ok(ref status) if status.addr == format!("127.0.0.1:{}", first) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    // failover announced by the sentinel
+    master.store(second as usize, Ordering::SeqCst);
+    let expected = format!("127.0.0.1:{}", second);
+    for _ in 0..50 {
+        tokio::time::delay_for(Duration::from_millis(20)).await;
+        if addr.send(Status).await.unwrap().addr == expected {
+            break;
+        }
+    }
+    let res = addr.send(Status).await;
+    match res {
+        Ok(ref status) if status.addr == expected => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+    let res = addr.send(Ping(None)).await;
+    match res {
+        Ok(Ok(_)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_sentinel_unknown_master() {
+    let master = Arc::new(AtomicUsize::new(0));
+    let sentinel = sentinel_server(master).await;
+
+    let addr = RedisActor::start_sentinel(Sentinel::new(
+        "othermaster",
+        vec![format!("127.0.0.1:{}", sentinel)],
+    ));
+
+    let res = addr.send(Status).await;
+    match res {
+        Ok(ref status)
+            if status
+                .last_error
+                .as_ref()
+                .map(|e| e.contains("unknown master othermaster"))
+                == Some(true) => {}
+        _ => panic!("Should not happen {:?}", res),
+    }
+    // no address is known, the name of the master is not one
+    let res = addr.send(Status).await;
+    match res {
+        Ok(ref status) if status.addr.is_empty() => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+    let res = addr
+        .send(BLPop {
+            keys: vec!["test".into()],
+            timeout: Duration::from_secs(1),
+        })
+        .await;
+    match res {
+        Ok(Err(Error::NotConnected)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_sentinel_unreachable() {
+    let first = pong_server().await;
+    let second = pong_server().await;
+    let master = Arc::new(AtomicUsize::new(first as usize));
+    let dying = dying_sentinel(first).await;
+    let sentinel = sentinel_server(master.clone()).await;
+
+    // the sentinel which knows the master is gone before the failovers are
+    // subscribed to
+    let addr = RedisActor::start_sentinel(Sentinel::new(
+        "mymaster",
+        vec![
+            format!("127.0.0.1:{}", dying),
+            format!("127.0.0.1:{}", sentinel),
+        ],
+    ));
+    let res = addr.send(Ping(None)).await;
+    match res {
+        Ok(Ok(_)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    // wait for the subscription to fall back to the other sentinel
+    tokio::time::delay_for(Duration::from_secs(2)).await;
+    master.store(second as usize, Ordering::SeqCst);
+    let expected = format!("127.0.0.1:{}", second);
+    for _ in 0..50 {
+        tokio::time::delay_for(Duration::from_millis(20)).await;
+        if addr.send(Status).await.unwrap().addr == expected {
+            break;
+        }
+    }
+    let res = addr.send(Status).await;
+    match res {
+        Ok(ref status) if status.addr == expected => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
@@ -0,0 +1,152 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_redis::redis::{ConnectionState, Shutdown, Status};
+use actix_redis::{command::*, Error, RedisActor, RedisClusterActor};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+mod common;
+use common::{bind, slow_server};
+
+// stand-in of a single node cluster counting CLUSTER SLOTS, which has no
+// value for any key
+async fn cluster_node() -> (String, Arc<AtomicUsize>) {
+    let (mut listener, port) = bind().await;
+    let slots = format!(
+        "*1\r\n*3\r\n:0\r\n:16383\r\n*2\r\n$9\r\n127.0.0.1\r\n:{}\r\n",
+        port
+    );
+    let refreshes = Arc::new(AtomicUsize::new(0));
+
+    let counter = refreshes.clone();
+    actix_rt::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let (slots, counter) = (slots.clone(), counter.clone());
+            actix_rt::spawn(async move {
+                let mut buf = [0; 1024];
+                while let Ok(n) = stream.read(&mut buf).await {
+                    let req = String::from_utf8_lossy(&buf[..n]).to_uppercase();
+                    let reply = if n == 0 {
+                        break;
+                    } else if req.contains("SLOTS") {
+                        counter.fetch_add(1, Ordering::SeqCst);
+                        slots.as_str()
+                    } else {
+                        "$-1\r\n"
+                    };
+                    if stream.write_all(reply.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    (format!("127.0.0.1:{}", port), refreshes)
+}
+
+#[actix_rt::test]
+async fn test_shutdown_drains_pending() {
+    let (port, connections) = slow_server(Duration::from_millis(200)).await;
+    let addr = RedisActor::start(format!("127.0.0.1:{}", port));
+
+    // wait for the connection
+    let res = addr.send(Ping(None)).await;
+    match res {
+        Ok(Ok(_)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    let pending = addr.send(Ping(None));
+    tokio::time::delay_for(Duration::from_millis(50)).await;
+    let shutdown = addr.send(Shutdown {
+        timeout: Duration::from_secs(5),
+    });
+
+    let res = addr.send(Ping(None)).await;
+    match res {
+        Ok(Err(Error::ShuttingDown)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    let res = pending.await;
+    match res {
+        Ok(Ok(_)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+    let res = shutdown.await;
+    match res {
+        Ok(Ok(())) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    // closed for good
+    tokio::time::delay_for(Duration::from_millis(200)).await;
+    let res = addr.send(Status).await;
+    match res {
+        Ok(ref status) if status.state == ConnectionState::Disconnected => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[actix_rt::test]
+async fn test_shutdown_deadline() {
+    let (port, _) = slow_server(Duration::from_secs(10)).await;
+    let addr = RedisActor::start(format!("127.0.0.1:{}", port));
+
+    let pending = addr.send(Ping(None));
+    tokio::time::delay_for(Duration::from_millis(50)).await;
+    let res = addr
+        .send(Shutdown {
+            timeout: Duration::from_millis(100),
+        })
+        .await;
+    match res {
+        Ok(Ok(())) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    let res = pending.await;
+    match res {
+        Ok(Err(Error::Disconnected)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_shutdown_cluster() {
+    let (node, refreshes) = cluster_node().await;
+    let addr = RedisClusterActor::builder()
+        .refresh_interval(Duration::from_millis(100))
+        .min_refresh_interval(Duration::from_millis(0))
+        .start(node);
+
+    let res = addr.send(Get { key: "test".into() }).await;
+    match res {
+        Ok(Ok(None)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    let res = addr
+        .send(Shutdown {
+            timeout: Duration::from_secs(1),
+        })
+        .await;
+    match res {
+        Ok(Ok(())) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    // the actor restarted by the supervisor neither reconnects nor refreshes
+    let res = addr.send(Get { key: "test".into() }).await;
+    match res {
+        Ok(Err(Error::ShuttingDown)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+    let before = refreshes.load(Ordering::SeqCst);
+    tokio::time::delay_for(Duration::from_millis(350)).await;
+    assert_eq!(refreshes.load(Ordering::SeqCst), before);
+}
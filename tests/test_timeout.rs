@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use actix_redis::transaction::{CheckAndSet, Transaction};
+use actix_redis::{command::*, redis::WithTimeout, Error, RedisActor};
+use futures::FutureExt;
+
+mod common;
+use common::stalled_server;
+
+#[actix_rt::test]
+async fn test_response_timeout() {
+    let port = stalled_server().await;
+
+    let addr = RedisActor::builder()
+        .response_timeout(Duration::from_millis(100))
+        .start(format!("127.0.0.1:{}", port));
+
+    let res = addr.send(Ping(None)).await;
+    match res {
+        Ok(Err(Error::Timeout)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_request_timeout() {
+    let port = stalled_server().await;
+
+    let addr = RedisActor::start(format!("127.0.0.1:{}", port));
+
+    let res = addr
+        .send(WithTimeout::new(Ping(None), Duration::from_millis(100)))
+        .await;
+    match res {
+        Ok(Err(Error::Timeout)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_check_and_set_timeout() {
+    let port = stalled_server().await;
+
+    let addr = RedisActor::builder()
+        .response_timeout(Duration::from_millis(100))
+        .start(format!("127.0.0.1:{}", port));
+
+    let cas = CheckAndSet::new(vec!["test".into()], |_| {
+        async { Ok(Transaction::new()) }.boxed_local()
+    });
+    let res = addr.send(cas).await;
+    match res {
+        Ok(Err(Error::Timeout)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
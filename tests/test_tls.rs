@@ -0,0 +1,106 @@
+#![cfg(feature = "tls")]
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_redis::{command::*, ConnectOptions, RedisActor, TlsConfig};
+use tokio_rustls::rustls::internal::pemfile;
+use tokio_rustls::rustls::{NoClientAuth, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+mod common;
+use common::{bind, reply_pong};
+
+const CA: &[u8] = include_bytes!("tls/ca.pem");
+const CERT: &[u8] = include_bytes!("tls/server.pem");
+const KEY: &[u8] = include_bytes!("tls/server.key");
+
+// TLS stand-in of a redis server answering every request with PONG
+async fn pong_server() -> u16 {
+    let mut config = ServerConfig::new(NoClientAuth::new());
+    let certs = pemfile::certs(&mut &CERT[..]).unwrap();
+    let key = pemfile::pkcs8_private_keys(&mut &KEY[..])
+        .unwrap()
+        .remove(0);
+    config.set_single_cert(certs, key).unwrap();
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+
+    let (mut listener, port) = bind().await;
+
+    actix_rt::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            if let Ok(stream) = acceptor.accept(stream).await {
+                actix_rt::spawn(reply_pong(stream, Duration::from_secs(0)));
+            }
+        }
+    });
+
+    port
+}
+
+#[actix_rt::test]
+async fn test_tls() {
+    let port = pong_server().await;
+
+    let addr = RedisActor::start_with(
+        format!("rediss://localhost:{}", port),
+        ConnectOptions {
+            tls: Some(TlsConfig::new().root_certificates(CA).unwrap()),
+            ..ConnectOptions::default()
+        },
+    );
+
+    let res = addr.send(Ping(None)).await;
+    match res {
+        Ok(Ok(pong)) => assert_eq!(pong, "PONG"),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_tls_untrusted() {
+    let port = pong_server().await;
+
+    // the self-signed test CA is not trusted by default
+    let addr = RedisActor::start(format!("rediss://localhost:{}", port));
+
+    let res = addr.send(Ping(None)).await;
+    match res {
+        Ok(Err(_)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_tls_ip_address() {
+    let port = pong_server().await;
+    let tls = TlsConfig::new().root_certificates(CA).unwrap();
+
+    // certificates can only be verified against DNS names
+    let addr = RedisActor::start_with(
+        format!("rediss://127.0.0.1:{}", port),
+        ConnectOptions {
+            tls: Some(tls.clone()),
+            ..ConnectOptions::default()
+        },
+    );
+    let res = addr.send(Ping(None)).await;
+    match res {
+        Ok(Err(_)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    let addr = RedisActor::start_with(
+        format!("rediss://127.0.0.1:{}", port),
+        ConnectOptions {
+            tls: Some(tls.server_name("localhost")),
+            ..ConnectOptions::default()
+        },
+    );
+    let res = addr.send(Ping(None)).await;
+    match res {
+        Ok(Ok(pong)) => assert_eq!(pong, "PONG"),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
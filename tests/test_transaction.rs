@@ -0,0 +1,170 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use actix::Addr;
+use actix_redis::transaction::{CheckAndSet, Transaction};
+use actix_redis::{command::*, Error, RedisActor, RespError};
+use futures::FutureExt;
+
+#[actix_rt::test]
+async fn test_transaction() {
+    env_logger::init();
+
+    let addr = RedisActor::start("127.0.0.1:6379");
+
+    let mut tx = Transaction::new();
+    let set = tx.add(Set {
+        key: "test-transaction".into(),
+        value: "41".into(),
+        expiration: Expiration::Infinite,
+    });
+    let incr = tx.add(Incr {
+        key: "test-transaction".into(),
+    });
+    let get = tx.add(Get {
+        key: "test-transaction".into(),
+    });
+
+    let res = addr.send(tx).await;
+    let replies = match res {
+        Ok(Ok(Some(replies))) => replies,
+        _ => panic!("Should not happen {:?}", res),
+    };
+
+    assert_eq!(replies.get(&set).unwrap(), ());
+    assert_eq!(replies.get(&incr).unwrap(), Ok(42));
+    assert_eq!(replies.get(&get).unwrap(), Some(b"42".to_vec()));
+}
+
+#[actix_rt::test]
+async fn test_transaction_command_error() {
+    let addr = RedisActor::start("127.0.0.1:6379");
+
+    let mut tx = Transaction::new();
+    tx.add(Set {
+        key: "test-transaction-error".into(),
+        value: "value".into(),
+        expiration: Expiration::Infinite,
+    });
+    let incr = tx.add(Incr {
+        key: "test-transaction-error".into(),
+    });
+    let del = tx.add(Del {
+        keys: vec!["test-transaction-error".into()],
+    });
+
+    let res = addr.send(tx).await;
+    let replies = match res {
+        Ok(Ok(Some(replies))) => replies,
+        _ => panic!("Should not happen {:?}", res),
+    };
+
+    // a failing command does not roll back the others
+    assert!(replies.get(&incr).unwrap().is_err());
+    assert_eq!(replies.get(&del).unwrap(), 1);
+}
+
+/// Bump `key` from another connection while it is watched
+async fn modify(addr: &Addr<RedisActor>, key: &str) -> Result<i64, Error> {
+    match addr.send(Incr { key: key.into() }).await {
+        Ok(Ok(res)) => res.map_err(|e| Error::Redis(RespError::Remote(e))),
+        Ok(Err(e)) => Err(e),
+        Err(_) => Err(Error::Disconnected),
+    }
+}
+
+#[actix_rt::test]
+async fn test_check_and_set() {
+    let addr = RedisActor::start("127.0.0.1:6379");
+    let res = addr
+        .send(Del {
+            keys: vec!["test-cas".into()],
+        })
+        .await;
+    match res {
+        Ok(Ok(_)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let cas = {
+        let addr = addr.clone();
+        let calls = calls.clone();
+        CheckAndSet::new(vec!["test-cas".into()], move |watcher| {
+            let addr = addr.clone();
+            let calls = calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                let value = watcher
+                    .send(Get {
+                        key: "test-cas".into(),
+                    })
+                    .await?;
+                // the first attempt has to be retried
+                if calls == 0 {
+                    modify(&addr, "test-cas").await?;
+                }
+
+                let value: i64 = value
+                    .map(|v| String::from_utf8_lossy(&v).parse().unwrap())
+                    .unwrap_or(0);
+                let mut tx = Transaction::new();
+                tx.add(Set {
+                    key: "test-cas".into(),
+                    value: (value * 10).to_string(),
+                    expiration: Expiration::Infinite,
+                });
+                Ok(tx)
+            }
+            .boxed_local()
+        })
+    };
+
+    let res = addr.send(cas).await;
+    match res {
+        Ok(Ok(Some(_))) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+    let res = addr
+        .send(Get {
+            key: "test-cas".into(),
+        })
+        .await;
+    match res {
+        Ok(Ok(Some(value))) => assert_eq!(value, b"10".to_vec()),
+        _ => panic!("Should not happen {:?}", res),
+    }
+}
+
+#[actix_rt::test]
+async fn test_check_and_set_max_retries() {
+    let addr = RedisActor::start("127.0.0.1:6379");
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let cas = {
+        let addr = addr.clone();
+        let calls = calls.clone();
+        CheckAndSet::new(vec!["test-cas-retries".into()], move |_watcher| {
+            let addr = addr.clone();
+            calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                modify(&addr, "test-cas-retries").await?;
+                let mut tx = Transaction::new();
+                tx.add(Del {
+                    keys: vec!["test-cas-retries".into()],
+                });
+                Ok(tx)
+            }
+            .boxed_local()
+        })
+        .max_retries(2)
+    };
+
+    let res = addr.send(cas).await;
+    match res {
+        Ok(Ok(None)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
@@ -0,0 +1,54 @@
+#![cfg(unix)]
+
+use std::time::Duration;
+
+use actix_redis::{command::*, Error, RedisActor};
+use tokio::net::UnixListener;
+use tokio::time::delay_for;
+
+mod common;
+use common::reply_pong;
+
+// stand-in of a redis server answering every request with PONG
+fn pong_server(path: &std::path::Path) {
+    let mut listener = UnixListener::bind(path).unwrap();
+
+    actix_rt::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            actix_rt::spawn(reply_pong(stream, Duration::from_secs(0)));
+        }
+    });
+}
+
+#[actix_rt::test]
+async fn test_unix_socket() {
+    let path =
+        std::env::temp_dir().join(format!("actix-redis-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let addr = RedisActor::start(format!("unix://{}", path.display()));
+
+    // nobody listens on the socket yet
+    let res = addr.send(Ping(None)).await;
+    match res {
+        Ok(Err(Error::NotConnected)) => (),
+        _ => panic!("Should not happen {:?}", res),
+    }
+
+    pong_server(&path);
+
+    // RedisActor eventually reconnects
+    loop {
+        match addr.send(Ping(None)).await {
+            Ok(Ok(pong)) => {
+                assert_eq!(pong, "PONG");
+                break;
+            }
+            Ok(Err(Error::NotConnected)) => delay_for(Duration::from_millis(100)).await,
+            res => panic!("Should not happen {:?}", res),
+        }
+    }
+
+    let _ = std::fs::remove_file(&path);
+}